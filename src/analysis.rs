@@ -0,0 +1,496 @@
+//! Analyses of sequences in homopolymer-compressed space.
+
+use crate::{homopolymer_compress, homopolymer_run_slices};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::ops::Range;
+
+/// Homopolymer compress the given sequence and count the k-mers of the compressed sequence in the same pass.
+///
+/// The k-mers are counted by sliding a window of length `k` over the compressed sequence.
+/// If the compressed sequence is shorter than `k`, then the k-mer profile is empty.
+///
+/// Panics if `k` is zero.
+pub fn homopolymer_compress_with_kmers(
+    input: &[u8],
+    k: usize,
+) -> (Vec<u8>, HashMap<Vec<u8>, usize>) {
+    assert!(k > 0, "k must be positive");
+    let mut compressed = Vec::new();
+    let mut kmers = HashMap::new();
+
+    for item in homopolymer_compress(input.iter().copied()) {
+        compressed.push(item);
+        if compressed.len() >= k {
+            let kmer = &compressed[compressed.len() - k..];
+            if let Some(count) = kmers.get_mut(kmer) {
+                *count += 1;
+            } else {
+                kmers.insert(kmer.to_vec(), 1);
+            }
+        }
+    }
+
+    (compressed, kmers)
+}
+
+/// Homopolymer compress both given sequences and return the indices in compressed space at which they differ.
+///
+/// Differences in the lengths of runs are ignored.
+/// If one compressed sequence is longer than the other, then all indices past the end of the shorter one are reported as differences.
+pub fn compressed_diff(a: &[u8], b: &[u8]) -> Vec<usize> {
+    let mut a = homopolymer_compress(a.iter().copied());
+    let mut b = homopolymer_compress(b.iter().copied());
+    let mut differences = Vec::new();
+    for index in 0.. {
+        match (a.next(), b.next()) {
+            (None, None) => break,
+            (a, b) if a == b => {}
+            _ => differences.push(index),
+        }
+    }
+    differences
+}
+
+/// Homopolymer compress both given sequences and compute the Hamming distance of the compressed sequences.
+///
+/// Differences in the lengths of runs are ignored, so e.g. `AACGGT` and `ACCGTT` have distance zero.
+/// Returns `None` if the compressed sequences have different lengths, since the Hamming distance is undefined then.
+pub fn compressed_hamming(a: &[u8], b: &[u8]) -> Option<usize> {
+    let mut a = homopolymer_compress(a.iter().copied());
+    let mut b = homopolymer_compress(b.iter().copied());
+    let mut distance = 0;
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Some(distance),
+            (Some(a), Some(b)) => distance += usize::from(a != b),
+            _ => return None,
+        }
+    }
+}
+
+/// Homopolymer compress both given sequences and find the first index in compressed space at which their symbols differ.
+///
+/// Returns the compressed index together with the ranges of the original query and reference covered by the differing runs.
+/// Differences in the lengths of runs are ignored, as in [`compressed_diff`].
+/// Returns `None` if the compressed sequences are equal, or if one of them is a prefix of the other.
+pub fn first_compressed_divergence(
+    query: &[u8],
+    reference: &[u8],
+) -> Option<(usize, Range<usize>, Range<usize>)> {
+    let mut query_start = 0;
+    let mut reference_start = 0;
+    for (index, (query_run, reference_run)) in homopolymer_run_slices(query)
+        .zip(homopolymer_run_slices(reference))
+        .enumerate()
+    {
+        let query_range = query_start..query_start + query_run.len();
+        let reference_range = reference_start..reference_start + reference_run.len();
+        if query_run[0] != reference_run[0] {
+            return Some((index, query_range, reference_range));
+        }
+        query_start = query_range.end;
+        reference_start = reference_range.end;
+    }
+    None
+}
+
+/// Find all homopolymer runs that are at least `min_length` long, e.g. to flag regions that are problematic for sequencing.
+///
+/// The runs are given as their symbol and length, as produced by [`homopolymer_compress_with_run_lengths`](crate::homopolymer_compress_with_run_lengths).
+/// Each long run is returned as its symbol together with the range of the uncompressed sequence it covers,
+/// which is 0-based and half-open as in the BED format.
+pub fn long_runs<Item>(
+    runs: impl IntoIterator<Item = (Item, usize)>,
+    min_length: usize,
+) -> impl Iterator<Item = (Item, Range<usize>)> {
+    let mut start = 0;
+    runs.into_iter().filter_map(move |(item, length)| {
+        let range = start..start + length;
+        start = range.end;
+        (length >= min_length).then(|| (item, range))
+    })
+}
+
+/// Compute the Shannon entropy of the symbol frequencies of the given sequence, in bits per symbol.
+///
+/// Returns 0.0 for an empty sequence.
+pub fn shannon_entropy(sequence: impl IntoIterator<Item = u8>) -> f64 {
+    let mut counts = [0usize; 256];
+    let mut len = 0;
+    for symbol in sequence {
+        counts[usize::from(symbol)] += 1;
+        len += 1;
+    }
+    if len == 0 {
+        return 0.0;
+    }
+
+    let len = len as f64;
+    -counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let frequency = count as f64 / len;
+            frequency * frequency.log2()
+        })
+        .sum::<f64>()
+}
+
+/// Homopolymer compress the given sequence and compute the Shannon entropy of the symbol frequencies of the compressed sequence, in bits per symbol.
+///
+/// The sequence is compressed and counted in a single pass.
+/// A low entropy indicates a low-complexity sequence, even after removing homopolymers.
+/// Returns 0.0 if the sequence is empty.
+pub fn compressed_entropy(input: impl IntoIterator<Item = u8>) -> f64 {
+    shannon_entropy(homopolymer_compress(input))
+}
+
+/// Homopolymer compress the given sequence and compute the GC content of the compressed sequence.
+///
+/// Each run is counted once, so this is the fraction of runs whose base is `G` or `C`, in upper or lower case.
+/// This differs from the GC content of the original sequence, where long runs weigh more than short ones.
+/// The sequence is compressed and counted in a single pass.
+/// Returns 0.0 if the sequence is empty.
+pub fn compressed_gc_content(input: impl IntoIterator<Item = u8>) -> f64 {
+    let mut gc = 0usize;
+    let mut runs = 0usize;
+    for base in homopolymer_compress(input) {
+        if matches!(base, b'G' | b'C' | b'g' | b'c') {
+            gc += 1;
+        }
+        runs += 1;
+    }
+    if runs == 0 {
+        0.0
+    } else {
+        gc as f64 / runs as f64
+    }
+}
+
+/// Homopolymer compress each given record and count the compression ratios of the records in `bins` buckets of equal width.
+///
+/// The compression ratio of a record is the length of its compressed sequence divided by the length of its original sequence, which is in `(0, 1]`.
+/// Bucket `i` covers the ratios in `[i / bins, (i + 1) / bins)`, except for the last bucket, which also includes a ratio of exactly one.
+/// The bucket of a ratio is computed with integer arithmetic, so ratios on a bucket edge are never rounded into the bucket below.
+/// Empty records are skipped, since their ratio is undefined.
+///
+/// Panics if `bins` is zero.
+pub fn compression_ratio_histogram<'record>(
+    records: impl IntoIterator<Item = &'record [u8]>,
+    bins: usize,
+) -> Vec<usize> {
+    assert!(bins > 0, "bins must be positive");
+    let mut histogram = vec![0; bins];
+    for record in records {
+        if record.is_empty() {
+            continue;
+        }
+        let compressed_len = homopolymer_compress(record.iter()).count();
+        histogram[(compressed_len * bins / record.len()).min(bins - 1)] += 1;
+    }
+    histogram
+}
+
+/// An accumulator of homopolymer run lengths that computes quantiles of all run lengths added to it.
+///
+/// Since run lengths are integers, the accumulator stores the number of runs of each distinct length,
+/// so its memory usage is proportional to the number of distinct run lengths instead of the number of runs.
+/// This is small even for genome-scale data, since a sequence of length `n` has less than `sqrt(2n)` distinct run lengths.
+/// Hence, the quantiles are exact rather than estimated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunLengthQuantiles {
+    counts: BTreeMap<usize, u64>,
+    runs: u64,
+}
+
+impl RunLengthQuantiles {
+    /// Create an empty accumulator.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a run of the given length.
+    pub fn add(&mut self, length: usize) {
+        *self.counts.entry(length).or_insert(0) += 1;
+        self.runs += 1;
+    }
+
+    /// Add all runs of another accumulator, e.g. one that was filled by a different thread.
+    pub fn merge(&mut self, other: &Self) {
+        for (&length, &count) in &other.counts {
+            *self.counts.entry(length).or_insert(0) += count;
+        }
+        self.runs += other.runs;
+    }
+
+    /// The number of runs added so far.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    /// The `q`-quantile of the run lengths added so far, for `q` between zero and one, or `None` if no runs were added.
+    ///
+    /// This is the smallest run length such that at least a fraction of `q` of all runs are at most as long (the nearest-rank method),
+    /// e.g. the median for a `q` of 0.5 and the shortest run length for a `q` of zero.
+    ///
+    /// Panics if `q` is not between zero and one.
+    pub fn quantile(&self, q: f64) -> Option<usize> {
+        assert!((0.0..=1.0).contains(&q), "q must be between zero and one");
+        let rank = ((q * self.runs as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (&length, &count) in &self.counts {
+            seen += count;
+            if seen >= rank {
+                return Some(length);
+            }
+        }
+        None
+    }
+}
+
+/// A record with its compression factor, ordered such that greater records are more compressible, and ties are broken by preferring the smaller id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RankedRecord {
+    id: String,
+    original_len: usize,
+    compressed_len: usize,
+}
+
+impl Ord for RankedRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Compare the factors exactly by cross-multiplying.
+        let factor = self.original_len as u128 * other.compressed_len as u128;
+        let other_factor = other.original_len as u128 * self.compressed_len as u128;
+        factor
+            .cmp(&other_factor)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for RankedRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// An accumulator of the `k` records with the highest compression factor, i.e. original length divided by compressed length.
+/// This is the inverse of the compression ratio used elsewhere, e.g. in [`compression_ratio_histogram`], so a higher factor means a stronger compression.
+///
+/// Records with equal factors are ranked by their id, such that the result does not depend on the order in which the records are added.
+/// Only `k` records are kept in memory at a time.
+#[derive(Debug, Clone)]
+pub struct MostCompressible {
+    k: usize,
+    /// The `k` best records seen so far, with the worst one on top.
+    records: BinaryHeap<Reverse<RankedRecord>>,
+}
+
+impl MostCompressible {
+    /// Create an empty accumulator of the `k` most compressible records.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            records: BinaryHeap::with_capacity(k.saturating_add(1).min(1024)),
+        }
+    }
+
+    /// Add a record with the given lengths of its original and compressed sequence.
+    /// Records with an empty compressed sequence have no compression factor and are ignored.
+    pub fn add(&mut self, id: &str, original_len: usize, compressed_len: usize) {
+        if compressed_len == 0 || self.k == 0 {
+            return;
+        }
+        let record = RankedRecord {
+            id: id.to_owned(),
+            original_len,
+            compressed_len,
+        };
+        if self.records.len() < self.k {
+            self.records.push(Reverse(record));
+        } else if let Some(mut worst) = self.records.peek_mut() {
+            if record > worst.0 {
+                *worst = Reverse(record);
+            }
+        }
+    }
+
+    /// The ids and compression factors of the most compressible records, in descending order of the factor.
+    pub fn into_sorted_vec(self) -> Vec<(String, f64)> {
+        self.records
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(record)| {
+                let factor = record.original_len as f64 / record.compressed_len as f64;
+                (record.id, factor)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{
+        compressed_diff, compressed_entropy, compressed_gc_content, compressed_hamming,
+        compression_ratio_histogram, first_compressed_divergence, homopolymer_compress_with_kmers,
+        long_runs, shannon_entropy, MostCompressible, RunLengthQuantiles,
+    };
+    use crate::homopolymer_compress_with_run_lengths;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_kmers() {
+        let (compressed, kmers) = homopolymer_compress_with_kmers(b"AACCCGAACGGA", 3);
+        assert_eq!(compressed, b"ACGACGA");
+        let expected: HashMap<_, _> = [
+            (b"ACG".to_vec(), 2),
+            (b"CGA".to_vec(), 2),
+            (b"GAC".to_vec(), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(kmers, expected);
+    }
+
+    #[test]
+    fn test_kmers_shorter_than_k() {
+        let (compressed, kmers) = homopolymer_compress_with_kmers(b"AAACCC", 3);
+        assert_eq!(compressed, b"AC");
+        assert!(kmers.is_empty());
+
+        let (compressed, kmers) = homopolymer_compress_with_kmers(b"", 1);
+        assert!(compressed.is_empty());
+        assert!(kmers.is_empty());
+    }
+
+    #[test]
+    fn test_compressed_diff() {
+        assert!(compressed_diff(b"AACGGGT", b"ACCCGTT").is_empty());
+        assert!(compressed_diff(b"", b"").is_empty());
+        assert_eq!(compressed_diff(b"AACGGT", b"ACCAAT"), [2]);
+        assert_eq!(compressed_diff(b"ACGT", b"AACCG"), [3]);
+        assert_eq!(compressed_diff(b"", b"AC"), [0, 1]);
+        assert_eq!(compressed_diff(b"AGT", b"ACGT"), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_compressed_hamming() {
+        // Only the run lengths differ.
+        assert_eq!(compressed_hamming(b"AACGGGT", b"ACCCGTT"), Some(0));
+        assert_eq!(compressed_hamming(b"", b""), Some(0));
+        assert_eq!(compressed_hamming(b"AACGGT", b"ACCAAT"), Some(1));
+        assert_eq!(compressed_hamming(b"AACGTT", b"CGGTAA"), Some(4));
+        assert_eq!(compressed_hamming(b"ACGT", b"AACCG"), None);
+        assert_eq!(compressed_hamming(b"", b"A"), None);
+    }
+
+    #[test]
+    fn test_first_compressed_divergence() {
+        // Compressed: ACGTA and ACGCA, differing at index 3.
+        assert_eq!(
+            first_compressed_divergence(b"AACGGGTA", b"ACCGCCCA"),
+            Some((3, 6..7, 4..7))
+        );
+        assert_eq!(
+            first_compressed_divergence(b"TTA", b"GA"),
+            Some((0, 0..2, 0..1))
+        );
+        assert_eq!(first_compressed_divergence(b"AACGT", b"ACCGGT"), None);
+        assert_eq!(first_compressed_divergence(b"ACG", b"AACGTT"), None);
+        assert_eq!(first_compressed_divergence(b"", b"ACG"), None);
+    }
+
+    #[test]
+    fn test_long_runs() {
+        let runs = homopolymer_compress_with_run_lengths(b"ACCAAAAAGTTTTTTC".iter().copied());
+        assert_eq!(
+            long_runs(runs, 5).collect::<Vec<_>>(),
+            vec![(b'A', 3..8), (b'T', 9..15)]
+        );
+        let runs = homopolymer_compress_with_run_lengths(b"ACCAAAAAGTTTTTTC".iter().copied());
+        assert_eq!(long_runs(runs, 6).collect::<Vec<_>>(), vec![(b'T', 9..15)]);
+        assert_eq!(long_runs(Vec::<(u8, usize)>::new(), 1).count(), 0);
+    }
+
+    #[test]
+    fn test_compressed_gc_content() {
+        assert_eq!(
+            compressed_gc_content(b"AAAAGGTT".iter().copied()),
+            1.0 / 3.0
+        );
+        assert_eq!(compressed_gc_content(b"GgCCc".iter().copied()), 1.0);
+        assert_eq!(compressed_gc_content(b"ATTTA".iter().copied()), 0.0);
+        assert_eq!(compressed_gc_content(b"".iter().copied()), 0.0);
+    }
+
+    #[test]
+    fn test_compression_ratio_histogram() {
+        // Ratios 1/4, 1/2, 3/4, 1 and 3/10, and an empty record.
+        let records: [&[u8]; 6] = [b"AAAA", b"AACC", b"AACG", b"ACGT", b"AAAAAAAACG", b""];
+        assert_eq!(compression_ratio_histogram(records, 4), [0, 2, 1, 2]);
+        assert_eq!(
+            compression_ratio_histogram(records, 10),
+            [0, 0, 1, 1, 0, 1, 0, 1, 0, 1]
+        );
+        assert_eq!(compression_ratio_histogram(records, 1), [5]);
+        assert_eq!(compression_ratio_histogram([], 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_run_length_quantiles() {
+        let mut quantiles = RunLengthQuantiles::new();
+        assert_eq!(quantiles.quantile(0.5), None);
+        for length in 1..=100 {
+            quantiles.add(length);
+        }
+        assert_eq!(quantiles.quantile(0.0), Some(1));
+        assert_eq!(quantiles.quantile(0.5), Some(50));
+        assert_eq!(quantiles.quantile(0.9), Some(90));
+        assert_eq!(quantiles.quantile(0.99), Some(99));
+        assert_eq!(quantiles.quantile(1.0), Some(100));
+
+        let mut other = RunLengthQuantiles::new();
+        for _ in 0..300 {
+            other.add(1);
+        }
+        quantiles.merge(&other);
+        assert_eq!(quantiles.runs(), 400);
+        assert_eq!(quantiles.quantile(0.75), Some(1));
+        assert_eq!(quantiles.quantile(0.9), Some(60));
+    }
+
+    #[test]
+    fn test_most_compressible() {
+        let mut most_compressible = MostCompressible::new(3);
+        most_compressible.add("r1", 4, 4);
+        most_compressible.add("r2", 12, 3);
+        most_compressible.add("r3", 0, 0);
+        most_compressible.add("r5", 6, 3);
+        most_compressible.add("r4", 4, 2);
+        most_compressible.add("r6", 9, 2);
+        assert_eq!(
+            most_compressible.into_sorted_vec(),
+            [
+                ("r6".to_string(), 4.5),
+                ("r2".to_string(), 4.0),
+                ("r4".to_string(), 2.0)
+            ]
+        );
+
+        let mut most_compressible = MostCompressible::new(0);
+        most_compressible.add("r1", 4, 1);
+        assert!(most_compressible.into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(shannon_entropy(b"".iter().copied()), 0.0);
+        assert_eq!(shannon_entropy(b"AAAA".iter().copied()), 0.0);
+        assert_eq!(shannon_entropy(b"ACGT".iter().copied()), 2.0);
+        assert_eq!(shannon_entropy(b"ACAC".iter().copied()), 1.0);
+
+        assert_eq!(compressed_entropy(b"".iter().copied()), 0.0);
+        assert_eq!(compressed_entropy(b"AAAAAAAC".iter().copied()), 1.0);
+        assert_eq!(compressed_entropy(b"AACCGGGGTT".iter().copied()), 2.0);
+    }
+}
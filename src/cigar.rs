@@ -0,0 +1,136 @@
+//! A CIGAR-like textual encoding of the homopolymer runs of a sequence.
+//!
+//! The encoding is a concatenation of all runs, where each run is written as its length in decimal followed by its symbol.
+//! For example, the runs of `AAACTT` are written as `3A1C2T`.
+//! The grammar is
+//!
+//! ```text
+//! cigar  = { run }
+//! run    = length symbol
+//! length = nonzero-digit { digit }
+//! symbol = any ASCII character that is not a digit
+//! ```
+//!
+//! Hence, the empty string encodes the empty sequence.
+//! Consecutive runs with the same symbol are allowed when parsing, even though they are never written for a homopolymer compressed sequence.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+
+/// Write the given runs in the CIGAR-like encoding.
+///
+/// Each run is given as its symbol and its length, as produced by [`homopolymer_compress_with_run_lengths`](crate::homopolymer_compress_with_run_lengths).
+/// Panics if a run has length zero or its symbol is not ASCII or an ASCII digit, since these cannot be encoded.
+pub fn to_cigar(runs: impl IntoIterator<Item = (u8, usize)>) -> String {
+    let mut cigar = String::new();
+    for (symbol, length) in runs {
+        assert!(length > 0, "runs must not be empty");
+        assert!(
+            symbol.is_ascii() && !symbol.is_ascii_digit(),
+            "symbols must be ASCII, but not ASCII digits"
+        );
+        write!(cigar, "{length}{}", char::from(symbol)).unwrap_or_else(|_| unreachable!());
+    }
+    cigar
+}
+
+/// An error that occurred while parsing the CIGAR-like encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CigarError {
+    /// The run starting at the given byte offset has no length.
+    MissingLength {
+        /// The byte offset of the run.
+        at: usize,
+    },
+    /// The run starting at the given byte offset has a length that is zero, starts with a zero, or does not fit into a `usize`.
+    InvalidLength {
+        /// The byte offset of the run.
+        at: usize,
+    },
+    /// The last run has no symbol.
+    MissingSymbol,
+}
+
+impl Display for CigarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingLength { at } => write!(f, "run at byte {at} has no length"),
+            Self::InvalidLength { at } => write!(f, "run at byte {at} has an invalid length"),
+            Self::MissingSymbol => write!(f, "last run has no symbol"),
+        }
+    }
+}
+
+impl Error for CigarError {}
+
+/// Parse the CIGAR-like encoding into runs given as their symbol and their length.
+///
+/// The runs can be expanded into the original sequence with [`homopolymer_expand`](crate::homopolymer_expand).
+pub fn parse_cigar(cigar: &[u8]) -> Result<Vec<(u8, usize)>, CigarError> {
+    let mut runs = Vec::new();
+    let mut at = 0;
+    while at < cigar.len() {
+        let digits = cigar[at..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return Err(CigarError::MissingLength { at });
+        }
+        let length = std::str::from_utf8(&cigar[at..at + digits])
+            .unwrap_or_else(|_| unreachable!())
+            .parse::<usize>()
+            .ok()
+            .filter(|_| cigar[at] != b'0')
+            .ok_or(CigarError::InvalidLength { at })?;
+        let symbol = *cigar.get(at + digits).ok_or(CigarError::MissingSymbol)?;
+        runs.push((symbol, length));
+        at += digits + 1;
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cigar::{parse_cigar, to_cigar, CigarError};
+    use crate::{homopolymer_compress_with_run_lengths, homopolymer_expand};
+
+    #[test]
+    fn test_round_trip() {
+        for input in [&b"AAACTT"[..], b"", b"A", b"ACGGGGGGGGGGGGT-N"] {
+            let cigar = to_cigar(homopolymer_compress_with_run_lengths(input.iter().copied()));
+            let runs = parse_cigar(cigar.as_bytes()).unwrap();
+            assert_eq!(homopolymer_expand(runs).collect::<Vec<_>>(), input);
+        }
+        assert_eq!(
+            to_cigar(homopolymer_compress_with_run_lengths(
+                b"AAACTT".iter().copied()
+            )),
+            "3A1C2T"
+        );
+        assert_eq!(
+            to_cigar(homopolymer_compress_with_run_lengths(
+                b"CAAAAAAAAAAAAG".iter().copied()
+            )),
+            "1C12A1G"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_cigar(b"A"), Err(CigarError::MissingLength { at: 0 }));
+        assert_eq!(
+            parse_cigar(b"3A0C"),
+            Err(CigarError::InvalidLength { at: 2 })
+        );
+        assert_eq!(
+            parse_cigar(b"3A01C"),
+            Err(CigarError::InvalidLength { at: 2 })
+        );
+        assert_eq!(
+            parse_cigar(b"99999999999999999999999A"),
+            Err(CigarError::InvalidLength { at: 0 })
+        );
+        assert_eq!(parse_cigar(b"3A2"), Err(CigarError::MissingSymbol));
+    }
+}
@@ -0,0 +1,65 @@
+//! Writing sequences as a single concatenated stream, e.g. for building an index over all sequences.
+
+use std::io::{Result, Write};
+
+/// A writer that concatenates sequences, terminating each one with a separator byte,
+/// and writes the position of each sequence to a tab-separated index.
+///
+/// The index has the columns `record_id`, `offset` and `length`,
+/// where `offset` is the position of the first byte of the sequence in the concatenated stream, and `length` excludes the separator.
+pub struct ConcatWriter<W: Write, I: Write> {
+    output: W,
+    index: I,
+    separator: u8,
+    offset: usize,
+}
+
+impl<W: Write, I: Write> ConcatWriter<W, I> {
+    /// Create a new writer, writing the header of the index immediately.
+    pub fn new(output: W, mut index: I, separator: u8) -> Result<Self> {
+        writeln!(index, "record_id\toffset\tlength")?;
+        Ok(Self {
+            output,
+            index,
+            separator,
+            offset: 0,
+        })
+    }
+
+    /// Append the given sequence followed by the separator, and add it to the index.
+    pub fn write_record(&mut self, id: &str, sequence: &[u8]) -> Result<()> {
+        writeln!(self.index, "{id}\t{}\t{}", self.offset, sequence.len())?;
+        self.output.write_all(sequence)?;
+        self.output.write_all(&[self.separator])?;
+        self.offset += sequence.len() + 1;
+        Ok(())
+    }
+
+    /// Flush the output and the index.
+    pub fn flush(&mut self) -> Result<()> {
+        self.output.flush()?;
+        self.index.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::concat::ConcatWriter;
+
+    #[test]
+    fn test_concat() {
+        let mut output = Vec::new();
+        let mut index = Vec::new();
+        let mut writer = ConcatWriter::new(&mut output, &mut index, b'$').unwrap();
+        writer.write_record("r1", b"ACGT").unwrap();
+        writer.write_record("r2", b"").unwrap();
+        writer.write_record("r3", b"TA").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(output, b"ACGT$$TA$");
+        assert_eq!(
+            String::from_utf8(index).unwrap(),
+            "record_id\toffset\tlength\nr1\t0\t4\nr2\t5\t0\nr3\t6\t2\n"
+        );
+    }
+}
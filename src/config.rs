@@ -0,0 +1,233 @@
+//! A serialisable configuration that bundles the options of homopolymer compression.
+
+use crate::homopolymer_compress_with_run_lengths;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::iter;
+
+/// The options of homopolymer compression, bundled such that they can be stored and applied consistently.
+///
+/// The default configuration compresses all homopolymers, like [`homopolymer_compress`](crate::homopolymer_compress).
+/// A run is compressed if it passes both `only` and `min_run`, and is then replaced by `normalize_length` copies of its item if set, or by at most `max_run` copies otherwise.
+/// Fields that are missing when deserialising take their default values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig<Item: Eq + Hash> {
+    /// If set, only runs of these items are compressed, and all other runs are kept unchanged.
+    /// See [`homopolymer_compress_only`](crate::homopolymer_compress_only).
+    pub only: Option<HashSet<Item>>,
+    /// Runs with fewer items are kept unchanged.
+    /// See [`homopolymer_compress_clamp`](crate::homopolymer_compress_clamp).
+    pub min_run: usize,
+    /// Compressed runs are shortened to at most this many copies of their item, which must be positive.
+    /// See [`homopolymer_compress_clamp`](crate::homopolymer_compress_clamp).
+    pub max_run: usize,
+    /// If set, compressed runs are replaced by exactly this many copies of their item, which takes precedence over `max_run`.
+    /// See [`homopolymer_normalize`](crate::homopolymer_normalize).
+    pub normalize_length: Option<usize>,
+}
+
+impl<Item: Eq + Hash + Clone> CompressionConfig<Item> {
+    /// Homopolymer compress the given sequence according to this configuration.
+    pub fn compress<Input: IntoIterator<Item = Item>>(&self, input: Input) -> Vec<Item> {
+        self.compress_with_run_lengths(input)
+            .map(|(item, _)| item)
+            .collect()
+    }
+
+    /// Homopolymer compress the given sequence according to this configuration, and compute the number of original items each compressed item stands for.
+    ///
+    /// Items of runs that are kept unchanged each stand for themselves only.
+    /// If a run is replaced by multiple copies of its item, then each copy stands for one original item, except for the last copy, which stands for the rest of the run.
+    /// Copies that lengthen a run with `normalize_length` stand for no original item, so their run length is zero.
+    ///
+    /// Panics if `max_run` is zero.
+    pub fn compress_with_run_lengths<'output, Input: 'output + IntoIterator<Item = Item>>(
+        &'output self,
+        input: Input,
+    ) -> impl 'output + Iterator<Item = (Item, usize)>
+    where
+        Item: 'output,
+    {
+        assert!(self.max_run > 0, "max_run must be positive");
+        homopolymer_compress_with_run_lengths(input).flat_map(move |(item, length)| {
+            let compress = length >= self.min_run
+                && self
+                    .only
+                    .as_ref()
+                    .map(|only| only.contains(&item))
+                    .unwrap_or(true);
+            let copies = if !compress {
+                length
+            } else if let Some(normalize_length) = self.normalize_length {
+                normalize_length
+            } else {
+                length.min(self.max_run)
+            };
+            iter::repeat(item)
+                .take(copies)
+                .enumerate()
+                .map(move |(copy, item)| {
+                    if copy + 1 == copies {
+                        (item, length.saturating_sub(copy))
+                    } else {
+                        (item, usize::from(copy < length))
+                    }
+                })
+        })
+    }
+}
+
+impl<Item: Eq + Hash> Default for CompressionConfig<Item> {
+    fn default() -> Self {
+        Self {
+            only: None,
+            min_run: 1,
+            max_run: 1,
+            normalize_length: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::CompressionConfig;
+    use crate::{homopolymer_compress, homopolymer_compress_clamp, homopolymer_normalize};
+
+    #[test]
+    fn test_default_config() {
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        let config = CompressionConfig::default();
+        assert_eq!(
+            config.compress(input.iter().copied()),
+            homopolymer_compress(input.iter().copied()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_only_config() {
+        let config = CompressionConfig {
+            only: Some([b'A'].into_iter().collect()),
+            ..Default::default()
+        };
+        assert_eq!(config.compress(b"AAACCCTAA".iter().copied()), b"ACCCTA");
+        let runs: Vec<_> = config
+            .compress_with_run_lengths(b"AAACCT".iter().copied())
+            .collect();
+        assert_eq!(runs, [(b'A', 3), (b'C', 1), (b'C', 1), (b'T', 1)]);
+    }
+
+    #[test]
+    fn test_serialisation() {
+        let config = CompressionConfig {
+            only: Some([b'A', b'T'].into_iter().collect()),
+            min_run: 3,
+            max_run: 2,
+            normalize_length: Some(4),
+        };
+        let serialised = serde_json::to_string(&config).unwrap();
+        let deserialised: CompressionConfig<u8> = serde_json::from_str(&serialised).unwrap();
+        assert_eq!(config, deserialised);
+
+        // Missing fields take their default values.
+        let deserialised: CompressionConfig<u8> = serde_json::from_str(r#"{"only":null}"#).unwrap();
+        assert_eq!(deserialised, CompressionConfig::default());
+    }
+
+    #[test]
+    fn test_clamp_config() {
+        let input = b"CAAAAAGGTTTCCCCCCA";
+        for (min_run, max_run) in [(1, 1), (3, 1), (2, 4), (3, 2), (0, 3)] {
+            let config = CompressionConfig {
+                min_run,
+                max_run,
+                ..Default::default()
+            };
+            assert_eq!(
+                config.compress(input.iter().copied()),
+                homopolymer_compress_clamp(input, min_run, max_run)
+            );
+            // The run lengths always add up to the original length.
+            let total: usize = config
+                .compress_with_run_lengths(input.iter().copied())
+                .map(|(_, length)| length)
+                .sum();
+            assert_eq!(total, input.len());
+        }
+        let config = CompressionConfig {
+            min_run: 3,
+            max_run: 2,
+            ..Default::default()
+        };
+        let runs: Vec<_> = config
+            .compress_with_run_lengths(b"GGAAAAT".iter().copied())
+            .collect();
+        assert_eq!(
+            runs,
+            [(b'G', 1), (b'G', 1), (b'A', 1), (b'A', 3), (b'T', 1)]
+        );
+    }
+
+    #[test]
+    fn test_normalize_config() {
+        let input = b"CAAAAAGGTTTCCCCCCA";
+        for normalize_length in [0, 1, 3] {
+            let config = CompressionConfig {
+                normalize_length: Some(normalize_length),
+                ..Default::default()
+            };
+            assert_eq!(
+                config.compress(input.iter().copied()),
+                homopolymer_normalize(input, normalize_length)
+            );
+        }
+        // Copies that lengthen a run stand for no original item.
+        let config = CompressionConfig {
+            normalize_length: Some(3),
+            ..Default::default()
+        };
+        let runs: Vec<_> = config
+            .compress_with_run_lengths(b"AAAAACG".iter().copied())
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                (b'A', 1),
+                (b'A', 1),
+                (b'A', 3),
+                (b'C', 1),
+                (b'C', 0),
+                (b'C', 0),
+                (b'G', 1),
+                (b'G', 0),
+                (b'G', 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combined_config() {
+        // Only runs of `A` with at least three items are compressed.
+        let config = CompressionConfig {
+            only: Some([b'A'].into_iter().collect()),
+            min_run: 3,
+            normalize_length: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.compress(b"AAAACCCCAAT".iter().copied()),
+            b"AACCCCAAT"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "max_run must be positive")]
+    fn test_zero_max_run() {
+        let config = CompressionConfig::<u8> {
+            max_run: 0,
+            ..Default::default()
+        };
+        let _ = config.compress_with_run_lengths(b"AA".iter().copied());
+    }
+}
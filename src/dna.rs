@@ -0,0 +1,309 @@
+//! A nucleotide alphabet for type-safe homopolymer compression of DNA.
+
+use crate::homopolymer_compress;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// A DNA nucleotide, including the unknown base `N`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Nucleotide {
+    /// Adenine.
+    A,
+    /// Cytosine.
+    C,
+    /// Guanine.
+    G,
+    /// Thymine.
+    T,
+    /// Unknown base.
+    N,
+}
+
+/// A byte that does not encode a nucleotide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidBase {
+    /// The offending byte.
+    pub base: u8,
+}
+
+impl TryFrom<u8> for Nucleotide {
+    type Error = InvalidBase;
+
+    /// Convert an ASCII byte into a nucleotide.
+    /// Both upper and lower case bytes are accepted.
+    fn try_from(base: u8) -> Result<Self, Self::Error> {
+        match base {
+            b'A' | b'a' => Ok(Self::A),
+            b'C' | b'c' => Ok(Self::C),
+            b'G' | b'g' => Ok(Self::G),
+            b'T' | b't' => Ok(Self::T),
+            b'N' | b'n' => Ok(Self::N),
+            base => Err(InvalidBase { base }),
+        }
+    }
+}
+
+impl From<Nucleotide> for u8 {
+    /// Convert a nucleotide into its upper case ASCII byte.
+    fn from(nucleotide: Nucleotide) -> Self {
+        match nucleotide {
+            Nucleotide::A => b'A',
+            Nucleotide::C => b'C',
+            Nucleotide::G => b'G',
+            Nucleotide::T => b'T',
+            Nucleotide::N => b'N',
+        }
+    }
+}
+
+impl From<Nucleotide> for char {
+    fn from(nucleotide: Nucleotide) -> Self {
+        u8::from(nucleotide).into()
+    }
+}
+
+impl Display for Nucleotide {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", char::from(*self))
+    }
+}
+
+impl Display for InvalidBase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid nucleotide: {:?}", char::from(self.base))
+    }
+}
+
+impl Error for InvalidBase {}
+
+/// Validate the given ASCII sequence as DNA and homopolymer compress it.
+///
+/// Bytes are converted with [`Nucleotide::try_from`], so upper and lower case versions of the same base belong to the same homopolymer.
+/// Returns the first byte that is not a nucleotide as error.
+pub fn compress_dna(input: &[u8]) -> Result<Vec<Nucleotide>, InvalidBase> {
+    homopolymer_compress(input.iter().copied().map(Nucleotide::try_from)).collect()
+}
+
+/// The IUPAC codes in the order of the sets of bases they stand for,
+/// where the set is a bitmask with `A = 1`, `C = 2`, `G = 4` and `T = 8`.
+const IUPAC_CODES: [u8; 16] = *b"-ACMGRSVTWYHKDBN";
+
+/// The set of bases the given IUPAC code stands for, as bitmask indexing [`IUPAC_CODES`].
+/// Returns `None` for bytes that are not IUPAC codes, and also for the gap `-`.
+/// `U` is treated as `T`, and lower case codes like their upper case versions.
+fn iupac_set(base: u8) -> Option<u8> {
+    match base.to_ascii_uppercase() {
+        b'-' => None,
+        b'U' => Some(8),
+        base => IUPAC_CODES
+            .iter()
+            .position(|&code| code == base)
+            .map(|set| set as u8),
+    }
+}
+
+/// Homopolymer compress the given DNA sequence with IUPAC ambiguity codes, treating compatible codes as part of the same run.
+///
+/// A base continues the current run if the set of bases it stands for overlaps with the set of the run.
+/// The set of the run is then narrowed to the intersection, and each run is output as the upper case IUPAC code of its final set.
+/// For example, `ARRG` compresses to `AG`, since `R` stands for `A` or `G`, and the intersection of `A` and `R` is `A`.
+/// Since runs are narrowed greedily from left to right, `RAG` compresses to `AG` as well.
+///
+/// Bytes that are not IUPAC codes, including gaps, only form runs with identical bytes, and are output unchanged.
+pub fn homopolymer_compress_iupac(input: &[u8]) -> Vec<u8> {
+    // The current run, either as set of bases or as a byte that is not an IUPAC code.
+    let mut run: Option<Result<u8, u8>> = None;
+    let mut compressed = Vec::new();
+    let mut emit = |run: Result<u8, u8>| {
+        compressed.push(match run {
+            Ok(set) => IUPAC_CODES[usize::from(set)],
+            Err(base) => base,
+        })
+    };
+
+    for &base in input {
+        let next = iupac_set(base).ok_or(base);
+        run = match (run, next) {
+            (Some(Ok(set)), Ok(next_set)) if set & next_set != 0 => Some(Ok(set & next_set)),
+            (Some(Err(other)), Err(next_other)) if other == next_other => run,
+            (previous, next) => {
+                if let Some(previous) = previous {
+                    emit(previous);
+                }
+                Some(next)
+            }
+        };
+    }
+    if let Some(run) = run {
+        emit(run);
+    }
+    compressed
+}
+
+/// Homopolymer compress the given sequence, treating the RNA base `U` as equal to the DNA base `T`.
+///
+/// This allows to compress mixed RNA and DNA sequences consistently.
+/// `U` and `T` are equal, and so are `u` and `t`, but case is respected otherwise as in [`homopolymer_compress`].
+/// Each run is output as its first byte, so a run that mixes `U` and `T` is output as `U` if it starts with `U`, and as `T` otherwise.
+/// For example, `UTTU` compresses to `U`, and `TUUT` compresses to `T`.
+pub fn homopolymer_compress_rna_dna(input: &[u8]) -> Vec<u8> {
+    let key = |base: u8| match base {
+        b'U' => b'T',
+        b'u' => b't',
+        base => base,
+    };
+    let mut previous = None;
+    input
+        .iter()
+        .copied()
+        .filter(|&base| previous.replace(key(base)) != Some(key(base)))
+        .collect()
+}
+
+/// The complement of the given DNA base, including IUPAC ambiguity codes, keeping its case.
+///
+/// For example, `A` becomes `T`, `r` becomes `y`, and `N` stays `N`.
+/// Bytes that are not IUPAC codes, e.g. gaps, are returned unchanged.
+pub fn complement_dna(base: u8) -> u8 {
+    let complement = match base.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'T' | b'U' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        _ => return base,
+    };
+    if base.is_ascii_lowercase() {
+        complement.to_ascii_lowercase()
+    } else {
+        complement
+    }
+}
+
+/// Reverse complement the given sequence and homopolymer compress it in a single pass, without allocating the reverse complement.
+///
+/// Each base is complemented with `complement`, e.g. [`complement_dna`].
+/// The result is equal to homopolymer compressing the reverse complement.
+pub fn homopolymer_compress_revcomp(input: &[u8], complement: impl Fn(u8) -> u8) -> Vec<u8> {
+    homopolymer_compress(input.iter().rev().map(|&base| complement(base))).collect()
+}
+
+/// Homopolymer compress the given sequence ignoring case, and output each run in the case of the majority of its bytes.
+///
+/// This keeps soft-masking, where lower case bases mark e.g. repeats, even if a run is only partially masked.
+/// If a run has as many upper as lower case bytes, then it is output in the case of its first byte.
+/// For example, `aAAA` compresses to `A`, while `aAAa` compresses to `a`.
+pub fn homopolymer_compress_majority_case(input: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    let mut remaining = input;
+    while let Some(&first) = remaining.first() {
+        let len = remaining
+            .iter()
+            .position(|base| !base.eq_ignore_ascii_case(&first))
+            .unwrap_or(remaining.len());
+        let (run, rest) = remaining.split_at(len);
+        let upper = run.iter().filter(|base| base.is_ascii_uppercase()).count();
+        let lower = run.iter().filter(|base| base.is_ascii_lowercase()).count();
+        compressed.push(match upper.cmp(&lower) {
+            Ordering::Greater => first.to_ascii_uppercase(),
+            Ordering::Less => first.to_ascii_lowercase(),
+            Ordering::Equal => first,
+        });
+        remaining = rest;
+    }
+    compressed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dna::{
+        complement_dna, compress_dna, homopolymer_compress_iupac,
+        homopolymer_compress_majority_case, homopolymer_compress_revcomp,
+        homopolymer_compress_rna_dna, InvalidBase, Nucleotide,
+    };
+    use crate::homopolymer_compress;
+
+    #[test]
+    fn test_nucleotide_conversion() {
+        for base in b"ACGTN".iter().copied() {
+            assert_eq!(u8::from(Nucleotide::try_from(base).unwrap()), base);
+            assert_eq!(
+                u8::from(Nucleotide::try_from(base.to_ascii_lowercase()).unwrap()),
+                base
+            );
+        }
+        assert_eq!(Nucleotide::try_from(b'U'), Err(InvalidBase { base: b'U' }));
+    }
+
+    #[test]
+    fn test_compress_dna() {
+        use Nucleotide::*;
+        assert_eq!(compress_dna(b"AACaaGTTTNnA"), Ok(vec![A, C, A, G, T, N, A]));
+        assert_eq!(compress_dna(b""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_compress_dna_invalid() {
+        assert_eq!(compress_dna(b"ACGTX"), Err(InvalidBase { base: b'X' }));
+        assert_eq!(compress_dna(b"AC-GT"), Err(InvalidBase { base: b'-' }));
+    }
+
+    #[test]
+    fn test_compress_iupac() {
+        assert_eq!(homopolymer_compress_iupac(b"AACGGT"), b"ACGT");
+        assert_eq!(homopolymer_compress_iupac(b"ARRG"), b"AG");
+        assert_eq!(homopolymer_compress_iupac(b"RAG"), b"AG");
+        assert_eq!(homopolymer_compress_iupac(b"RKY"), b"GY");
+        assert_eq!(homopolymer_compress_iupac(b"NNNC"), b"C");
+        assert_eq!(homopolymer_compress_iupac(b"acuTt"), b"ACT");
+        assert_eq!(homopolymer_compress_iupac(b"A--XXN"), b"A-XN");
+        assert_eq!(homopolymer_compress_iupac(b""), b"");
+    }
+
+    #[test]
+    fn test_compress_rna_dna() {
+        assert_eq!(homopolymer_compress_rna_dna(b"UTTU"), b"U");
+        assert_eq!(homopolymer_compress_rna_dna(b"TUUT"), b"T");
+        assert_eq!(homopolymer_compress_rna_dna(b"AUUTCutG"), b"AUCuG");
+        assert_eq!(homopolymer_compress_rna_dna(b"TtUu"), b"TtUu");
+        assert_eq!(homopolymer_compress_rna_dna(b""), b"");
+    }
+
+    #[test]
+    fn test_compress_revcomp() {
+        for input in [&b"AACGGGTTA"[..], b"acgtNNrykm-", b"TTTT", b""] {
+            let revcomp: Vec<_> = input
+                .iter()
+                .rev()
+                .map(|&base| complement_dna(base))
+                .collect();
+            assert_eq!(
+                homopolymer_compress_revcomp(input, complement_dna),
+                homopolymer_compress(revcomp).collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(
+            homopolymer_compress_revcomp(b"AACGGGTTA", complement_dna),
+            b"TACGT"
+        );
+        assert_eq!(complement_dna(b'r'), b'y');
+        assert_eq!(complement_dna(b'-'), b'-');
+    }
+
+    #[test]
+    fn test_compress_majority_case() {
+        assert_eq!(homopolymer_compress_majority_case(b"aAAa"), b"a");
+        assert_eq!(homopolymer_compress_majority_case(b"AaaA"), b"A");
+        assert_eq!(homopolymer_compress_majority_case(b"aAAA"), b"A");
+        assert_eq!(homopolymer_compress_majority_case(b"AaaCcGT-"), b"aCGT-");
+        assert_eq!(homopolymer_compress_majority_case(b""), b"");
+    }
+}
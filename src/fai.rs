@@ -0,0 +1,254 @@
+//! Random access to regions of fasta files using a samtools fasta index (`.fai`).
+//!
+//! A `.fai` file is tab-separated, with one row per sequence and the columns name, length, offset, line bases and line width,
+//! where offset is the byte position of the first base of the sequence in the fasta file,
+//! and line width is the number of bytes of each full line including its line terminator.
+//! Such an index can be created with `samtools faidx`.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, ErrorKind, Read, Seek, SeekFrom};
+use std::str::FromStr;
+
+/// The index entry of a single sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiRecord {
+    /// The name of the sequence.
+    pub name: String,
+    /// The number of bases of the sequence.
+    pub length: usize,
+    /// The byte position of the first base of the sequence in the fasta file.
+    pub offset: u64,
+    /// The number of bases of each full line.
+    pub line_bases: usize,
+    /// The number of bytes of each full line, including its line terminator.
+    pub line_width: usize,
+}
+
+impl FaiRecord {
+    /// The byte position of the base at the given 0-based position in the fasta file.
+    fn byte_offset(&self, position: usize) -> u64 {
+        self.offset
+            + ((position / self.line_bases) * self.line_width + position % self.line_bases) as u64
+    }
+}
+
+/// A fasta index, mapping sequence names to their positions in the fasta file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaiIndex {
+    records: Vec<FaiRecord>,
+}
+
+impl FaiIndex {
+    /// Read an index in `.fai` format.
+    pub fn read(reader: impl BufRead) -> std::io::Result<Self> {
+        let invalid = |line_number: usize, message: &str| {
+            std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("line {line_number} of fasta index: {message}"),
+            )
+        };
+
+        let mut records = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let columns: Vec<_> = line.split('\t').collect();
+            if columns.len() < 5 {
+                return Err(invalid(index + 1, "expected at least five columns"));
+            }
+            let number = |column: usize| {
+                columns[column]
+                    .parse::<usize>()
+                    .map_err(|error| invalid(index + 1, &error.to_string()))
+            };
+            let record = FaiRecord {
+                name: columns[0].to_owned(),
+                length: number(1)?,
+                offset: number(2)? as u64,
+                line_bases: number(3)?,
+                line_width: number(4)?,
+            };
+            if record.line_bases == 0 || record.line_width <= record.line_bases {
+                return Err(invalid(
+                    index + 1,
+                    "line width must be greater than line bases, which must be positive",
+                ));
+            }
+            records.push(record);
+        }
+        Ok(Self { records })
+    }
+
+    /// Get the index entry of the sequence with the given name.
+    pub fn get(&self, name: &str) -> Option<&FaiRecord> {
+        self.records.iter().find(|record| record.name == name)
+    }
+
+    /// Read the bases of the given region from the indexed fasta file.
+    pub fn read_region<R: Read + Seek>(
+        &self,
+        fasta: &mut R,
+        region: &Region,
+    ) -> Result<Vec<u8>, RegionError> {
+        let record = self
+            .get(&region.name)
+            .ok_or_else(|| RegionError::UnknownSequence(region.name.clone()))?;
+        let (start, end) = match region.range {
+            Some((_, end)) if end > record.length => {
+                return Err(RegionError::OutOfBounds {
+                    region: region.clone(),
+                    length: record.length,
+                })
+            }
+            Some((start, end)) => (start - 1, end),
+            None => (0, record.length),
+        };
+        if start == end {
+            return Ok(Vec::new());
+        }
+
+        let first_byte = record.byte_offset(start);
+        let last_byte = record.byte_offset(end - 1);
+        fasta
+            .seek(SeekFrom::Start(first_byte))
+            .map_err(RegionError::Io)?;
+        let mut bytes = vec![0; (last_byte - first_byte + 1) as usize];
+        fasta.read_exact(&mut bytes).map_err(RegionError::Io)?;
+        bytes.retain(|&byte| byte != b'\n' && byte != b'\r');
+        Ok(bytes)
+    }
+}
+
+/// A region of a sequence, written as `name:start-end` with 1-based inclusive coordinates as in samtools, or as `name` for the whole sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The name of the sequence.
+    pub name: String,
+    /// The 1-based inclusive start and end of the region, or `None` for the whole sequence.
+    pub range: Option<(usize, usize)>,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(region: &str) -> Result<Self, Self::Err> {
+        let range = region.rsplit_once(':').and_then(|(name, range)| {
+            let (start, end) = range.split_once('-')?;
+            Some((
+                name,
+                start.parse::<usize>().ok()?,
+                end.parse::<usize>().ok()?,
+            ))
+        });
+        if let Some((name, start, end)) = range {
+            if start == 0 || start > end {
+                return Err(format!(
+                    "region {region} must have 1 <= start <= end, since coordinates are 1-based and inclusive"
+                ));
+            }
+            Ok(Self {
+                name: name.to_owned(),
+                range: Some((start, end)),
+            })
+        } else if region.is_empty() {
+            Err("region must not be empty".to_owned())
+        } else {
+            Ok(Self {
+                name: region.to_owned(),
+                range: None,
+            })
+        }
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some((start, end)) = self.range {
+            write!(f, "{}:{start}-{end}", self.name)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+/// An error that occurred while reading a region.
+#[derive(Debug)]
+pub enum RegionError {
+    /// The index contains no sequence of the given name.
+    UnknownSequence(String),
+    /// The region ends after the end of its sequence.
+    OutOfBounds {
+        /// The region.
+        region: Region,
+        /// The length of the sequence.
+        length: usize,
+    },
+    /// The fasta file could not be read.
+    Io(std::io::Error),
+}
+
+impl Display for RegionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownSequence(name) => write!(f, "no sequence named {name} in the index"),
+            Self::OutOfBounds { region, length } => write!(
+                f,
+                "region {region} is out of bounds, since {} has length {length}",
+                region.name
+            ),
+            Self::Io(error) => write!(f, "cannot read region: {error}"),
+        }
+    }
+}
+
+impl Error for RegionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fai::{FaiIndex, Region, RegionError};
+    use std::io::Cursor;
+
+    const FASTA: &[u8] = b">chr1 first\nAACCG\nGTTTA\nC\n>chr2\r\nACGTA\r\nCG\r\n";
+    const FAI: &[u8] = b"chr1\t11\t12\t5\t6\nchr2\t7\t33\t5\t7\n";
+
+    #[test]
+    fn test_read_region() {
+        let index = FaiIndex::read(FAI).unwrap();
+        assert_eq!(index.get("chr2").unwrap().offset, 33);
+        let mut fasta = Cursor::new(FASTA);
+        let mut read = |region: &str| index.read_region(&mut fasta, &region.parse().unwrap());
+
+        assert_eq!(read("chr1").unwrap(), b"AACCGGTTTAC");
+        assert_eq!(read("chr1:4-8").unwrap(), b"CGGTT");
+        assert_eq!(read("chr1:11-11").unwrap(), b"C");
+        assert_eq!(read("chr2:5-7").unwrap(), b"ACG");
+        assert_eq!(read("chr2").unwrap(), b"ACGTACG");
+        assert!(matches!(
+            read("chr1:5-12"),
+            Err(RegionError::OutOfBounds { length: 11, .. })
+        ));
+        assert!(matches!(read("chr3"), Err(RegionError::UnknownSequence(_))));
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let region: Region = "chrUn:1:10-20".parse().unwrap();
+        assert_eq!(region.name, "chrUn:1");
+        assert_eq!(region.range, Some((10, 20)));
+        assert_eq!(region.to_string(), "chrUn:1:10-20");
+        assert_eq!("chr1".parse::<Region>().unwrap().range, None);
+        assert!("chr1:0-5".parse::<Region>().is_err());
+        assert!("chr1:6-5".parse::<Region>().is_err());
+        assert!("".parse::<Region>().is_err());
+    }
+}
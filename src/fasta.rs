@@ -0,0 +1,321 @@
+//! Reading and writing fasta records without holding their sequences in memory.
+
+use crate::HomopolymerCompressor;
+use std::collections::HashSet;
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+
+/// The number of bytes written at once if lines are not wrapped.
+const UNWRAPPED_CHUNK_SIZE: usize = 8192;
+
+/// A fasta writer that consumes sequences as iterators and wraps them into lines of a fixed width.
+///
+/// At most one line of the sequence is held in memory at a time.
+pub struct FastaWriter<W: Write> {
+    writer: W,
+    line_width: usize,
+    line: Vec<u8>,
+}
+
+impl<W: Write> FastaWriter<W> {
+    /// Create a new fasta writer that wraps sequences into lines of `line_width` bytes.
+    /// If `line_width` is zero, then each sequence is written on a single line.
+    pub fn new(writer: W, line_width: usize) -> Self {
+        Self {
+            writer,
+            line_width,
+            line: Vec::new(),
+        }
+    }
+
+    /// Write a fasta record with the given id, optional description and sequence.
+    pub fn write_record(
+        &mut self,
+        id: &str,
+        description: Option<&str>,
+        sequence: impl IntoIterator<Item = u8>,
+    ) -> Result<()> {
+        self.writer.write_all(b">")?;
+        self.writer.write_all(id.as_bytes())?;
+        if let Some(description) = description {
+            self.writer.write_all(b" ")?;
+            self.writer.write_all(description.as_bytes())?;
+        }
+        self.writer.write_all(b"\n")?;
+
+        let chunk_size = if self.line_width == 0 {
+            UNWRAPPED_CHUNK_SIZE
+        } else {
+            self.line_width
+        };
+        let mut is_empty = true;
+        for item in sequence {
+            self.line.push(item);
+            if self.line.len() == chunk_size {
+                self.writer.write_all(&self.line)?;
+                if self.line_width != 0 {
+                    self.writer.write_all(b"\n")?;
+                }
+                self.line.clear();
+                is_empty = false;
+            }
+        }
+
+        if !self.line.is_empty() || is_empty || self.line_width == 0 {
+            self.writer.write_all(&self.line)?;
+            self.writer.write_all(b"\n")?;
+            self.line.clear();
+        }
+        Ok(())
+    }
+
+    /// Flush the writer, ensuring that everything is written.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Homopolymer compress all records of the given fasta input while streaming it, and write the compressed records to `output`.
+///
+/// Unlike reading whole records, this holds neither whole records nor whole lines in memory, but only the buffers of `input` and `output`,
+/// such that records of arbitrary length can be compressed, e.g. a whole genome as a single record on a single line.
+/// The sequence lines of a record are fed into a [`HomopolymerCompressor`], such that runs that span multiple lines are compressed into a single item.
+/// Each header line finishes the current record and starts a new compressor, such that runs never span multiple records.
+///
+/// Each byte of the sequences is passed through `transform` before compressing, e.g. to convert it to upper case.
+/// Header lines are copied unchanged, and empty lines and carriage returns are dropped.
+/// The compressed sequences are wrapped into lines of `line_width` bytes like with [`FastaWriter`], or written on a single line if `line_width` is zero.
+/// Sequence bytes before the first header are an error.
+///
+/// Returns the number of records.
+pub fn compress_fasta_streaming(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    line_width: usize,
+    mut transform: impl FnMut(u8) -> u8,
+) -> Result<usize> {
+    let mut records = 0;
+    let mut compressor = HomopolymerCompressor::new();
+    let mut is_line_start = true;
+    let mut is_header = false;
+    // The number of bytes of the current output line and of the current compressed sequence.
+    let mut column = 0;
+    let mut written = 0;
+    let mut buffer = Vec::new();
+
+    let finish_record = |buffer: &mut Vec<u8>, column: usize, written: usize| {
+        if column > 0 || written == 0 {
+            buffer.push(b'\n');
+        }
+    };
+
+    loop {
+        let chunk = match input.fill_buf() {
+            Ok(chunk) => chunk,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        if chunk.is_empty() {
+            break;
+        }
+
+        for &byte in chunk {
+            if is_header {
+                if byte == b'\n' {
+                    buffer.push(b'\n');
+                    is_header = false;
+                    is_line_start = true;
+                } else if byte != b'\r' {
+                    buffer.push(byte);
+                }
+            } else if byte == b'>' && is_line_start {
+                if records > 0 {
+                    finish_record(&mut buffer, column, written);
+                }
+                buffer.push(b'>');
+                records += 1;
+                compressor = HomopolymerCompressor::new();
+                column = 0;
+                written = 0;
+                is_header = true;
+                is_line_start = false;
+            } else if byte == b'\n' {
+                is_line_start = true;
+            } else if byte != b'\r' {
+                if records == 0 {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "expected a fasta header starting with '>' before the first sequence",
+                    ));
+                }
+                is_line_start = false;
+                if let Some(item) = compressor.push(transform(byte)) {
+                    buffer.push(item);
+                    column += 1;
+                    written += 1;
+                    if column == line_width {
+                        buffer.push(b'\n');
+                        column = 0;
+                    }
+                }
+            }
+        }
+
+        let len = chunk.len();
+        input.consume(len);
+        output.write_all(&buffer)?;
+        buffer.clear();
+    }
+
+    if is_header {
+        buffer.push(b'\n');
+    }
+    if records > 0 {
+        finish_record(&mut buffer, column, written);
+    }
+    output.write_all(&buffer)?;
+    Ok(records)
+}
+
+/// The point at which writing a partially written fasta file can be resumed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResumePoint {
+    /// The ids of the records that were written completely.
+    pub ids: HashSet<String>,
+    /// The byte offset after the last completely written record, at which writing should continue.
+    pub offset: u64,
+}
+
+/// Scan a partially written fasta file for the ids of its records.
+///
+/// Since writing may have been interrupted in the middle of the last record, the last record is never considered complete.
+/// Resuming from the returned offset hence writes the last record again.
+pub fn find_resume_point(mut reader: impl BufRead) -> Result<ResumePoint> {
+    let mut ids = HashSet::new();
+    let mut last_record: Option<(String, u64)> = None;
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let len = reader.read_until(b'\n', &mut line)?;
+        if len == 0 {
+            break;
+        }
+        if let Some(header) = line.strip_prefix(b">") {
+            let header = String::from_utf8_lossy(header);
+            let id = header.split_whitespace().next().unwrap_or("").to_owned();
+            if let Some((id, _)) = last_record.replace((id, offset)) {
+                ids.insert(id);
+            }
+        }
+        offset += len as u64;
+    }
+
+    Ok(ResumePoint {
+        ids,
+        offset: last_record.map(|(_, offset)| offset).unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fasta::{compress_fasta_streaming, find_resume_point, FastaWriter};
+    use crate::homopolymer_compress;
+    use bio::io::fasta;
+    use std::io::BufReader;
+
+    fn write(line_width: usize, sequence: &[u8]) -> String {
+        let mut output = Vec::new();
+        let mut writer = FastaWriter::new(&mut output, line_width);
+        writer
+            .write_record("id", Some("desc"), sequence.iter().copied())
+            .unwrap();
+        writer
+            .write_record("id2", None, b"AC".iter().copied())
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_line_wrapping() {
+        assert_eq!(write(3, b"ACGTACG"), ">id desc\nACG\nTAC\nG\n>id2\nAC\n");
+        assert_eq!(write(3, b"ACGTAC"), ">id desc\nACG\nTAC\n>id2\nAC\n");
+        assert_eq!(write(3, b""), ">id desc\n\n>id2\nAC\n");
+    }
+
+    #[test]
+    fn test_find_resume_point() {
+        let resume_point = find_resume_point(&b">r1 desc\nACG\nT\n>r2\n\n>r3\nAC"[..]).unwrap();
+        assert_eq!(resume_point.ids.len(), 2);
+        assert!(resume_point.ids.contains("r1") && resume_point.ids.contains("r2"));
+        assert_eq!(resume_point.offset, 20);
+
+        assert_eq!(find_resume_point(&b""[..]).unwrap(), Default::default());
+        assert_eq!(
+            find_resume_point(&b">r1\nAC"[..]).unwrap(),
+            Default::default()
+        );
+    }
+
+    fn compress_streaming(input: &[u8], line_width: usize) -> String {
+        let mut output = Vec::new();
+        // A buffer of one byte splits the input between reads at every possible position.
+        let input = BufReader::with_capacity(1, input);
+        compress_fasta_streaming(input, &mut output, line_width, |byte| byte).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_compress_streaming() {
+        let input = b"\n>r1 desc\nAAAC\nCCGT\r\nT\n\n>r2\n>r3\nGG";
+        assert_eq!(
+            compress_streaming(input, 3),
+            ">r1 desc\nACG\nT\n>r2\n\n>r3\nG\n"
+        );
+        assert_eq!(
+            compress_streaming(input, 0),
+            ">r1 desc\nACGT\n>r2\n\n>r3\nG\n"
+        );
+        assert_eq!(compress_streaming(b">r1\nAACGGT\n", 4), ">r1\nACGT\n");
+        assert_eq!(compress_streaming(b">r1", 4), ">r1\n\n");
+        assert_eq!(compress_streaming(b"", 4), "");
+    }
+
+    #[test]
+    fn test_compress_streaming_matches_writer() {
+        let input = b">r1 desc\nAACCGGTTTACG\n>r2\n\n>r3\nAC";
+        let mut expected = Vec::new();
+        let mut writer = FastaWriter::new(&mut expected, 4);
+        for record in fasta::Reader::new(&input[..]).records() {
+            let record = record.unwrap();
+            writer
+                .write_record(
+                    record.id(),
+                    record.desc(),
+                    homopolymer_compress(record.seq().iter().copied()),
+                )
+                .unwrap();
+        }
+        assert_eq!(
+            compress_streaming(input, 4),
+            String::from_utf8(expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_compress_streaming_transform() {
+        let mut output = Vec::new();
+        let records = compress_fasta_streaming(&b">r1\naAAc\n"[..], &mut output, 0, |byte| {
+            byte.to_ascii_uppercase()
+        })
+        .unwrap();
+        assert_eq!(records, 1);
+        assert_eq!(output, b">r1\nAC\n");
+        assert!(compress_fasta_streaming(&b"ACGT\n"[..], &mut output, 0, |byte| byte).is_err());
+    }
+
+    #[test]
+    fn test_no_line_wrapping() {
+        assert_eq!(write(0, b"ACGTACG"), ">id desc\nACGTACG\n>id2\nAC\n");
+        assert_eq!(write(0, b""), ">id desc\n\n>id2\nAC\n");
+    }
+}
@@ -0,0 +1,624 @@
+//! Maps to homopolymer decompress compressed sequences.
+
+pub mod packed;
+
+use crate::homopolymer_expand;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{ErrorKind, Write};
+use std::iter;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A non-cryptographic hash function used to detect if a compressed sequence is decompressed with the wrong map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// The 64-bit FNV-1a hash.
+    Fnv1a64,
+    /// The CRC-32 checksum as used by zlib and gzip.
+    Crc32,
+}
+
+/// The lookup table for computing CRC-32 checksums bytewise.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                (value >> 1) ^ 0xEDB8_8320
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+};
+
+impl ChecksumAlgorithm {
+    /// Compute the checksum of the given sequence.
+    pub fn checksum(&self, sequence: &[u8]) -> u64 {
+        match self {
+            Self::Fnv1a64 => sequence.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+            }),
+            Self::Crc32 => {
+                let crc = sequence.iter().fold(0xFFFF_FFFFu32, |crc, &byte| {
+                    CRC32_TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+                });
+                u64::from(!crc)
+            }
+        }
+    }
+}
+
+impl Default for ChecksumAlgorithm {
+    fn default() -> Self {
+        Self::Fnv1a64
+    }
+}
+
+/// The name of a [`ChecksumAlgorithm`] could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownChecksumAlgorithm(pub String);
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = UnknownChecksumAlgorithm;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "fnv1a64" => Ok(Self::Fnv1a64),
+            "crc32" => Ok(Self::Crc32),
+            other => Err(UnknownChecksumAlgorithm(other.to_string())),
+        }
+    }
+}
+
+impl Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Fnv1a64 => "fnv1a64",
+                Self::Crc32 => "crc32",
+            }
+        )
+    }
+}
+
+impl Display for UnknownChecksumAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown checksum algorithm {:?}, expected one of fnv1a64, crc32",
+            self.0
+        )
+    }
+}
+
+impl Error for UnknownChecksumAlgorithm {}
+
+/// The checksum of an original sequence, together with the algorithm that computed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksum {
+    /// The algorithm used to compute the checksum.
+    pub algorithm: ChecksumAlgorithm,
+    /// The value of the checksum.
+    pub value: u64,
+}
+
+impl Checksum {
+    /// Compute the checksum of the given sequence with the given algorithm.
+    pub fn compute(algorithm: ChecksumAlgorithm, sequence: &[u8]) -> Self {
+        Self {
+            algorithm,
+            value: algorithm.checksum(sequence),
+        }
+    }
+
+    /// Returns true if the given sequence has this checksum.
+    pub fn verify(&self, sequence: &[u8]) -> bool {
+        self.algorithm.checksum(sequence) == self.value
+    }
+}
+
+/// The map to homopolymer decompress a single compressed record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HodecoMap {
+    /// The id of the record.
+    pub id: String,
+    /// For each item of the compressed sequence, the index of the start of its run in the original sequence.
+    pub runs: Vec<usize>,
+    /// The length of the original sequence.
+    pub original_len: usize,
+    /// The checksum of the original sequence, if any.
+    pub checksum: Option<Checksum>,
+}
+
+/// A hodeco map is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HodecoMapError {
+    /// The first run does not start at zero, or the map has no runs but the original sequence is not empty.
+    NotStartingAtZero,
+    /// The run at the given position of the map does not start after the previous run.
+    NotStrictlyIncreasing {
+        /// The position of the run in the map.
+        at: usize,
+    },
+    /// The run at the given position of the map does not start before the end of the original sequence.
+    IndexOutOfRange {
+        /// The position of the run in the map.
+        index: usize,
+    },
+}
+
+/// Check that the given run starts are a well-formed hodeco map of an original sequence of the given length.
+///
+/// The run starts must start at zero, be strictly increasing and be less than the original length.
+pub fn validate_hodeco_map(map: &[usize], original_len: usize) -> Result<(), HodecoMapError> {
+    match map.first() {
+        None if original_len == 0 => return Ok(()),
+        Some(0) => {}
+        _ => return Err(HodecoMapError::NotStartingAtZero),
+    }
+    for (index, &start) in map.iter().enumerate() {
+        if index > 0 && start <= map[index - 1] {
+            return Err(HodecoMapError::NotStrictlyIncreasing { at: index });
+        }
+        if start >= original_len {
+            return Err(HodecoMapError::IndexOutOfRange { index });
+        }
+    }
+    Ok(())
+}
+
+/// An error that occurred while homopolymer decompressing a sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecompressionError {
+    /// The map is malformed.
+    InvalidMap(HodecoMapError),
+    /// The length of the compressed sequence does not match the number of runs in the map.
+    LengthMismatch {
+        /// The length of the compressed sequence.
+        compressed_len: usize,
+        /// The number of runs in the map.
+        runs: usize,
+    },
+    /// The decompressed sequence does not match the checksum stored in the map.
+    ChecksumMismatch {
+        /// The checksum stored in the map.
+        expected: Checksum,
+        /// The checksum of the decompressed sequence.
+        actual: u64,
+    },
+}
+
+impl HodecoMap {
+    /// Create the map of a record from the lengths of the runs of its original sequence.
+    pub fn from_run_lengths(
+        id: String,
+        run_lengths: impl IntoIterator<Item = usize>,
+        checksum: Option<Checksum>,
+    ) -> Self {
+        let mut original_len = 0;
+        let runs = run_lengths
+            .into_iter()
+            .map(|length| {
+                let start = original_len;
+                original_len += length;
+                start
+            })
+            .collect();
+        Self {
+            id,
+            runs,
+            original_len,
+            checksum,
+        }
+    }
+
+    /// The run starts followed by the length of the original sequence.
+    /// This is the representation of the map used in the cbor hodeco map format.
+    pub fn mapping(&self) -> Vec<usize> {
+        let mut mapping = self.runs.clone();
+        mapping.push(self.original_len);
+        mapping
+    }
+
+    /// The lengths of the runs of the original sequence.
+    pub fn run_lengths(&self) -> impl '_ + Iterator<Item = usize> {
+        self.runs
+            .iter()
+            .zip(self.runs.iter().skip(1).chain(Some(&self.original_len)))
+            .map(|(start, end)| end - start)
+    }
+
+    /// Homopolymer decompress the given compressed sequence with this map.
+    /// The map is validated with [`validate_hodeco_map`] first.
+    /// If the map has a checksum, then the decompressed sequence is verified against it.
+    pub fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, DecompressionError> {
+        let decompressed = apply_hodeco_map(compressed, self)?;
+        if let Some(checksum) = self.checksum {
+            if !checksum.verify(&decompressed) {
+                return Err(DecompressionError::ChecksumMismatch {
+                    expected: checksum,
+                    actual: checksum.algorithm.checksum(&decompressed),
+                });
+            }
+        }
+        Ok(decompressed)
+    }
+}
+
+/// Expand the given compressed sequence with the run structure of the given map, e.g. to reconstruct the coordinates of a different sequence with the same run structure.
+///
+/// The map is validated with [`validate_hodeco_map`] first, and the compressed sequence must have exactly one symbol per run of the map.
+/// Unlike [`HodecoMap::decompress`], the checksum of the map is ignored, since it belongs to the sequence the map was created from.
+pub fn apply_hodeco_map(compressed: &[u8], map: &HodecoMap) -> Result<Vec<u8>, DecompressionError> {
+    validate_hodeco_map(&map.runs, map.original_len).map_err(DecompressionError::InvalidMap)?;
+    if compressed.len() != map.runs.len() {
+        return Err(DecompressionError::LengthMismatch {
+            compressed_len: compressed.len(),
+            runs: map.runs.len(),
+        });
+    }
+    Ok(homopolymer_expand(compressed.iter().copied().zip(map.run_lengths())).collect())
+}
+
+/// Homopolymer decompress the given compressed sequence with the explicit length of each of its runs.
+///
+/// Unlike a hodeco map, which stores the start of each run and hence needs the length of the original sequence for the last run,
+/// the counts determine every run on their own, such that `counts` must have exactly one entry per item of `compressed`.
+/// The counts are the run lengths produced by [`homopolymer_compress_with_run_lengths`](crate::homopolymer_compress_with_run_lengths).
+/// Runs with a count of zero are skipped, as in [`homopolymer_expand`].
+pub fn homopolymer_decompress_counted<Item: Clone>(
+    compressed: &[Item],
+    counts: &[usize],
+) -> Result<Vec<Item>, DecompressionError> {
+    if compressed.len() != counts.len() {
+        return Err(DecompressionError::LengthMismatch {
+            compressed_len: compressed.len(),
+            runs: counts.len(),
+        });
+    }
+    Ok(homopolymer_expand(compressed.iter().cloned().zip(counts.iter().copied())).collect())
+}
+
+/// The number of bytes that [`homopolymer_decompress_streaming`] buffers before writing them.
+const STREAMING_CHUNK_SIZE: usize = 8192;
+
+/// Homopolymer decompress the given compressed sequence with the explicit length of each of its runs, and write the decompressed bytes to `output` while decompressing.
+///
+/// This is the streaming version of [`homopolymer_decompress_counted`], which holds at most a few kilobytes of the decompressed sequence in memory,
+/// such that also very long runs and sequences are decompressed with constant memory.
+/// The compressed sequence and the counts are consumed in lockstep, and must have the same length.
+/// Otherwise, an error of kind [`ErrorKind::InvalidInput`] is returned, after all runs until the shorter of the two ended have been written.
+pub fn homopolymer_decompress_streaming<Item: Into<u8>, W: Write>(
+    compressed: impl IntoIterator<Item = Item>,
+    counts: impl IntoIterator<Item = usize>,
+    output: &mut W,
+) -> std::io::Result<()> {
+    let mut compressed = compressed.into_iter();
+    let mut counts = counts.into_iter();
+    let mut buffer = Vec::with_capacity(STREAMING_CHUNK_SIZE);
+    loop {
+        let (item, mut count) = match (compressed.next(), counts.next()) {
+            (Some(item), Some(count)) => (item.into(), count),
+            (None, None) => break,
+            _ => {
+                output.write_all(&buffer)?;
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "the compressed sequence and the counts have different lengths",
+                ));
+            }
+        };
+        while count > 0 {
+            let len = count.min(STREAMING_CHUNK_SIZE - buffer.len());
+            buffer.extend(iter::repeat(item).take(len));
+            count -= len;
+            if buffer.len() == STREAMING_CHUNK_SIZE {
+                output.write_all(&buffer)?;
+                buffer.clear();
+            }
+        }
+    }
+    output.write_all(&buffer)
+}
+
+/// A lookup structure for translating between original and compressed coordinates, built from a hodeco map.
+///
+/// Both directions take logarithmic time in the length of the compressed sequence at most.
+/// Coordinates outside of the respective sequence result in `None` rather than being clamped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HodecoIndex {
+    /// The starts of all runs in the original sequence, followed by the length of the original sequence.
+    prefix_sums: Vec<usize>,
+}
+
+impl HodecoIndex {
+    /// Build the index of the given map, validating the map with [`validate_hodeco_map`].
+    pub fn new(map: &HodecoMap) -> Result<Self, HodecoMapError> {
+        validate_hodeco_map(&map.runs, map.original_len)?;
+        let mut prefix_sums = map.runs.clone();
+        prefix_sums.push(map.original_len);
+        Ok(Self { prefix_sums })
+    }
+
+    /// The length of the compressed sequence.
+    pub fn compressed_len(&self) -> usize {
+        self.prefix_sums.len() - 1
+    }
+
+    /// The length of the original sequence.
+    pub fn original_len(&self) -> usize {
+        self.prefix_sums[self.prefix_sums.len() - 1]
+    }
+
+    /// The index in the compressed sequence of the run that contains the given index of the original sequence,
+    /// or `None` if the index is not smaller than the length of the original sequence.
+    pub fn compressed_index_of(&self, original_index: usize) -> Option<usize> {
+        if original_index >= self.original_len() {
+            return None;
+        }
+        Some(match self.prefix_sums.binary_search(&original_index) {
+            Ok(run) => run,
+            Err(next_run) => next_run - 1,
+        })
+    }
+
+    /// The range of indices of the original sequence covered by the run at the given index of the compressed sequence,
+    /// or `None` if the index is not smaller than the length of the compressed sequence.
+    pub fn original_range_of(&self, compressed_index: usize) -> Option<Range<usize>> {
+        let start = *self.prefix_sums.get(compressed_index)?;
+        let end = *self.prefix_sums.get(compressed_index + 1)?;
+        Some(start..end)
+    }
+}
+
+impl Display for HodecoMapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotStartingAtZero => write!(f, "hodeco map does not start at zero"),
+            Self::NotStrictlyIncreasing { at } => {
+                write!(f, "hodeco map is not strictly increasing at position {at}")
+            }
+            Self::IndexOutOfRange { index } => write!(
+                f,
+                "hodeco map entry at position {index} is not less than the original length"
+            ),
+        }
+    }
+}
+
+impl Error for HodecoMapError {}
+
+impl Display for DecompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidMap(error) => write!(f, "invalid hodeco map: {error}"),
+            Self::LengthMismatch {
+                compressed_len,
+                runs,
+            } => write!(
+                f,
+                "compressed sequence has length {compressed_len}, but the map has {runs} runs"
+            ),
+            Self::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "decompressed sequence has {} checksum {actual:#x}, but expected {:#x}",
+                expected.algorithm, expected.value
+            ),
+        }
+    }
+}
+
+impl Error for DecompressionError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::hodeco::{
+        apply_hodeco_map, homopolymer_decompress_counted, homopolymer_decompress_streaming,
+        validate_hodeco_map, Checksum, ChecksumAlgorithm, DecompressionError, HodecoIndex,
+        HodecoMap, HodecoMapError, STREAMING_CHUNK_SIZE,
+    };
+    use crate::{homopolymer_compress_with_hodeco_map, homopolymer_compress_with_run_lengths};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_checksum_algorithms() {
+        assert_eq!(ChecksumAlgorithm::Crc32.checksum(b"123456789"), 0xCBF4_3926);
+        assert_eq!(ChecksumAlgorithm::Crc32.checksum(b""), 0);
+        assert_eq!(
+            ChecksumAlgorithm::Fnv1a64.checksum(b"a"),
+            0xAF63_DC4C_8601_EC8C
+        );
+        assert_eq!(
+            ChecksumAlgorithm::Fnv1a64.checksum(b""),
+            0xCBF2_9CE4_8422_2325
+        );
+    }
+
+    fn compress_with_map(input: &[u8], algorithm: ChecksumAlgorithm) -> (Vec<u8>, HodecoMap) {
+        let (compressed, runs) =
+            homopolymer_compress_with_hodeco_map(input.iter().copied()).unzip();
+        let map = HodecoMap {
+            id: "id".to_string(),
+            runs,
+            original_len: input.len(),
+            checksum: Some(Checksum::compute(algorithm, input)),
+        };
+        (compressed, map)
+    }
+
+    #[test]
+    fn test_decompress_with_checksum() {
+        for algorithm in [ChecksumAlgorithm::Fnv1a64, ChecksumAlgorithm::Crc32] {
+            let input = b"AACGGGTAAC";
+            let (compressed, map) = compress_with_map(input, algorithm);
+            assert_eq!(map.decompress(&compressed).unwrap(), input);
+
+            // A different sequence with the same run structure.
+            let (other_compressed, _) = compress_with_map(b"TTGCCCATTG", algorithm);
+            assert!(matches!(
+                map.decompress(&other_compressed),
+                Err(DecompressionError::ChecksumMismatch { .. })
+            ));
+
+            assert_eq!(
+                map.decompress(b"ACG"),
+                Err(DecompressionError::LengthMismatch {
+                    compressed_len: 3,
+                    runs: 6
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_counted() {
+        for input in [&b"AACGGGTAAC"[..], b"A", b""] {
+            let (compressed, counts): (Vec<_>, Vec<_>) =
+                homopolymer_compress_with_run_lengths(input.iter().copied()).unzip();
+            assert_eq!(
+                homopolymer_decompress_counted(&compressed, &counts).unwrap(),
+                input
+            );
+        }
+        assert_eq!(
+            homopolymer_decompress_counted(b"ACG", &[1, 2]),
+            Err(DecompressionError::LengthMismatch {
+                compressed_len: 3,
+                runs: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_decompress_streaming() {
+        let long_run = vec![b'G'; 3 * STREAMING_CHUNK_SIZE + 5];
+        let long_input = [&b"AAC"[..], &long_run, b"TA"].concat();
+        for input in [&b"AACGGGTAAC"[..], b"", &long_input] {
+            let (compressed, counts): (Vec<_>, Vec<_>) =
+                homopolymer_compress_with_run_lengths(input.iter().copied()).unzip();
+            let mut output = Vec::new();
+            homopolymer_decompress_streaming(compressed, counts, &mut output).unwrap();
+            assert_eq!(output, input);
+        }
+
+        let mut output = Vec::new();
+        let error = homopolymer_decompress_streaming(b"ACG".iter().copied(), [2, 1], &mut output)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(output, b"AAC");
+    }
+
+    #[test]
+    fn test_apply_hodeco_map() {
+        let (_, map) = compress_with_map(b"AACGGGTAAC", ChecksumAlgorithm::default());
+        // The checksum of the map does not match, but is ignored.
+        assert_eq!(apply_hodeco_map(b"TGCATG", &map).unwrap(), b"TTGCCCATTG");
+        assert_eq!(
+            apply_hodeco_map(b"TGCAT", &map),
+            Err(DecompressionError::LengthMismatch {
+                compressed_len: 5,
+                runs: 6
+            })
+        );
+        assert_eq!(
+            apply_hodeco_map(b"TGCATGC", &map),
+            Err(DecompressionError::LengthMismatch {
+                compressed_len: 7,
+                runs: 6
+            })
+        );
+    }
+
+    #[test]
+    fn test_hodeco_index() {
+        // Runs AA C GGG T A.
+        let map = HodecoMap::from_run_lengths("id".to_string(), [2, 1, 3, 1, 1], None);
+        let index = HodecoIndex::new(&map).unwrap();
+        assert_eq!(index.compressed_len(), 5);
+        assert_eq!(index.original_len(), 8);
+
+        let compressed_indices: Vec<_> = (0..8)
+            .map(|original_index| index.compressed_index_of(original_index).unwrap())
+            .collect();
+        assert_eq!(compressed_indices, [0, 0, 1, 2, 2, 2, 3, 4]);
+        assert_eq!(index.compressed_index_of(8), None);
+        assert_eq!(index.compressed_index_of(usize::MAX), None);
+
+        assert_eq!(index.original_range_of(0), Some(0..2));
+        assert_eq!(index.original_range_of(2), Some(3..6));
+        assert_eq!(index.original_range_of(4), Some(7..8));
+        assert_eq!(index.original_range_of(5), None);
+
+        let empty =
+            HodecoIndex::new(&HodecoMap::from_run_lengths("id".to_string(), [], None)).unwrap();
+        assert_eq!(empty.compressed_index_of(0), None);
+        assert_eq!(empty.original_range_of(0), None);
+
+        let invalid = HodecoMap {
+            id: "id".to_string(),
+            runs: vec![1],
+            original_len: 2,
+            checksum: None,
+        };
+        assert_eq!(
+            HodecoIndex::new(&invalid),
+            Err(HodecoMapError::NotStartingAtZero)
+        );
+    }
+
+    #[test]
+    fn test_validate_hodeco_map() {
+        assert_eq!(validate_hodeco_map(&[0, 2, 3], 5), Ok(()));
+        assert_eq!(validate_hodeco_map(&[], 0), Ok(()));
+        assert_eq!(
+            validate_hodeco_map(&[], 1),
+            Err(HodecoMapError::NotStartingAtZero)
+        );
+        assert_eq!(
+            validate_hodeco_map(&[1, 2], 5),
+            Err(HodecoMapError::NotStartingAtZero)
+        );
+        assert_eq!(
+            validate_hodeco_map(&[0, 2, 2], 5),
+            Err(HodecoMapError::NotStrictlyIncreasing { at: 2 })
+        );
+        assert_eq!(
+            validate_hodeco_map(&[0, 3, 1], 5),
+            Err(HodecoMapError::NotStrictlyIncreasing { at: 2 })
+        );
+        assert_eq!(
+            validate_hodeco_map(&[0, 2, 5], 5),
+            Err(HodecoMapError::IndexOutOfRange { index: 2 })
+        );
+
+        let map = HodecoMap {
+            id: "id".to_string(),
+            runs: vec![0, 3, 1],
+            original_len: 5,
+            checksum: None,
+        };
+        assert_eq!(
+            map.decompress(b"ACG"),
+            Err(DecompressionError::InvalidMap(
+                HodecoMapError::NotStrictlyIncreasing { at: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_decompress_empty() {
+        let (compressed, map) = compress_with_map(b"", ChecksumAlgorithm::default());
+        assert_eq!(map.decompress(&compressed).unwrap(), b"");
+    }
+}
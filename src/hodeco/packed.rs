@@ -0,0 +1,432 @@
+//! A compact binary format for hodeco maps.
+//!
+//! A packed file starts with the eight byte header `HOCOMAP1`, followed by one frame per record, a zero byte that marks the end of the frames, an index and a footer.
+//! All integers are unsigned LEB128 varints, except in the footer.
+//! Each frame consists of
+//!  * the length of the record id in bytes plus one, followed by the UTF-8 bytes of the id,
+//!  * the number of runs, followed by the length of each run, i.e. the differences between consecutive run starts,
+//!  * a checksum tag, which is 0 for no checksum, 1 for fnv1a64 and 2 for crc32,
+//!    followed by the checksum as eight little-endian bytes if the tag is not 0.
+//!
+//! Since runs are almost always short, most run lengths are stored in a single byte.
+//!
+//! The index consists of the number of records, followed by the byte offset of each frame in the file, each stored as the difference to the offset of the previous frame.
+//! It is written after the frames, since the maps are written while the records are compressed, and the number of records is not known before.
+//! The fixed-size footer consists of the byte offset of the index as eight little-endian bytes, followed by the eight bytes `HOCOIDX1`.
+//! Hence, [`PackedHodecoMapIndex`] can find the index from the end of the file, and then read the map of any record without reading the frames before it.
+
+use crate::hodeco::{Checksum, ChecksumAlgorithm, HodecoMap};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+/// The bytes every packed hodeco map file starts with.
+pub const PACKED_HEADER: &[u8; 8] = b"HOCOMAP1";
+
+/// The bytes every packed hodeco map file ends with, after the offset of the index.
+pub const PACKED_INDEX_FOOTER: &[u8; 8] = b"HOCOIDX1";
+
+/// The length of the footer, i.e. the offset of the index and [`PACKED_INDEX_FOOTER`].
+const FOOTER_LEN: usize = 8 + PACKED_INDEX_FOOTER.len();
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> Result<()> {
+    let mut buffer = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer[len] = byte;
+            len += 1;
+            break;
+        } else {
+            buffer[len] = byte | 0x80;
+            len += 1;
+        }
+    }
+    writer.write_all(&buffer[..len])
+}
+
+/// Read a varint, or return `None` if the reader is at its end before the first byte.
+fn read_varint(reader: &mut impl Read) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0];
+        if reader.read(&mut byte)? == 0 {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err(Error::new(ErrorKind::UnexpectedEof, "truncated varint"))
+            };
+        }
+        // The tenth byte holds only the highest bit of a u64, so any other bit would overflow.
+        if shift >= 64 || (shift == 63 && byte[0] & 0x7E != 0) {
+            return Err(Error::new(ErrorKind::InvalidData, "varint too long"));
+        }
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+        shift += 7;
+    }
+}
+
+fn read_required_varint(reader: &mut impl Read) -> Result<u64> {
+    read_varint(reader)?
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "truncated hodeco map frame"))
+}
+
+fn read_usize(reader: &mut impl Read) -> Result<usize> {
+    usize::try_from(read_required_varint(reader)?)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "integer too large"))
+}
+
+/// A writer for hodeco maps in the packed format.
+///
+/// The index is only written by [`finish`](Self::finish), so a file that is not finished cannot be read completely.
+pub struct PackedHodecoMapWriter<W: Write> {
+    writer: W,
+    /// The number of bytes written so far.
+    offset: u64,
+    frame_offsets: Vec<u64>,
+    /// A buffer for the frame that is currently written, to know its length.
+    frame: Vec<u8>,
+}
+
+impl<W: Write> PackedHodecoMapWriter<W> {
+    /// Create a new writer, writing the file header immediately.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(PACKED_HEADER)?;
+        Ok(Self {
+            writer,
+            offset: PACKED_HEADER.len() as u64,
+            frame_offsets: Vec::new(),
+            frame: Vec::new(),
+        })
+    }
+
+    /// Write the hodeco map of a single record.
+    pub fn write(&mut self, map: &HodecoMap) -> Result<()> {
+        let frame = &mut self.frame;
+        frame.clear();
+        write_varint(frame, map.id.len() as u64 + 1)?;
+        frame.extend_from_slice(map.id.as_bytes());
+        write_varint(frame, map.runs.len() as u64)?;
+        for run_length in map.run_lengths() {
+            write_varint(frame, run_length as u64)?;
+        }
+        match map.checksum {
+            None => frame.push(0),
+            Some(Checksum { algorithm, value }) => {
+                frame.push(match algorithm {
+                    ChecksumAlgorithm::Fnv1a64 => 1,
+                    ChecksumAlgorithm::Crc32 => 2,
+                });
+                frame.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        self.writer.write_all(frame)?;
+        self.frame_offsets.push(self.offset);
+        self.offset += frame.len() as u64;
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    /// Write the end of the frames, the index and the footer, flush, and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let mut tail = vec![0];
+        let index_offset = self.offset + 1;
+        write_varint(&mut tail, self.frame_offsets.len() as u64)?;
+        let mut previous_offset = 0;
+        for &offset in &self.frame_offsets {
+            write_varint(&mut tail, offset - previous_offset)?;
+            previous_offset = offset;
+        }
+        tail.extend_from_slice(&index_offset.to_le_bytes());
+        tail.extend_from_slice(PACKED_INDEX_FOOTER);
+        self.writer.write_all(&tail)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Read a frame, or return `None` at the zero byte that marks the end of the frames.
+fn read_frame(reader: &mut impl Read) -> Result<Option<HodecoMap>> {
+    let id_len = match read_required_varint(reader)? {
+        0 => return Ok(None),
+        id_len => id_len - 1,
+    };
+    let mut id = Vec::new();
+    reader.take(id_len).read_to_end(&mut id)?;
+    if id.len() as u64 != id_len {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated record id"));
+    }
+    let id = String::from_utf8(id).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+    let run_count = read_usize(reader)?;
+    let run_lengths = (0..run_count)
+        .map(|_| read_usize(reader))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut tag = [0];
+    reader.read_exact(&mut tag)?;
+    let algorithm = match tag[0] {
+        0 => None,
+        1 => Some(ChecksumAlgorithm::Fnv1a64),
+        2 => Some(ChecksumAlgorithm::Crc32),
+        tag => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown checksum tag {tag}"),
+            ))
+        }
+    };
+    let checksum = if let Some(algorithm) = algorithm {
+        let mut value = [0; 8];
+        reader.read_exact(&mut value)?;
+        Some(Checksum {
+            algorithm,
+            value: u64::from_le_bytes(value),
+        })
+    } else {
+        None
+    };
+
+    Ok(Some(HodecoMap::from_run_lengths(id, run_lengths, checksum)))
+}
+
+/// A reader for hodeco maps in the packed format, iterating over the maps of all records in the order of the file.
+///
+/// The reader stops at the end of the frames, without reading the index.
+pub struct PackedHodecoMapReader<R: Read> {
+    reader: R,
+    finished: bool,
+}
+
+impl<R: Read> PackedHodecoMapReader<R> {
+    /// Create a new reader, reading and verifying the file header immediately.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0; PACKED_HEADER.len()];
+        reader.read_exact(&mut header)?;
+        if &header != PACKED_HEADER {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not a packed hodeco map file",
+            ));
+        }
+        Ok(Self {
+            reader,
+            finished: false,
+        })
+    }
+}
+
+impl<R: Read> Iterator for PackedHodecoMapReader<R> {
+    type Item = Result<HodecoMap>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result = read_frame(&mut self.reader).transpose();
+        // Stop at the end of the frames, and after an error, since the position of the next frame is unknown.
+        self.finished = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
+/// The index of a packed hodeco map file, which allows to read the map of any record without reading the maps before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedHodecoMapIndex {
+    frame_offsets: Vec<u64>,
+}
+
+impl PackedHodecoMapIndex {
+    /// Read the index through the footer at the end of the given file.
+    pub fn read(reader: &mut (impl Read + Seek)) -> Result<Self> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        if file_len < (PACKED_HEADER.len() + FOOTER_LEN) as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "too short for a packed hodeco map file",
+            ));
+        }
+        reader.seek(SeekFrom::Start(file_len - FOOTER_LEN as u64))?;
+        let mut footer = [0; FOOTER_LEN];
+        reader.read_exact(&mut footer)?;
+        if &footer[8..] != PACKED_INDEX_FOOTER {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "missing packed hodeco map index, the file may not have been finished",
+            ));
+        }
+        let mut index_offset = [0; 8];
+        index_offset.copy_from_slice(&footer[..8]);
+        reader.seek(SeekFrom::Start(u64::from_le_bytes(index_offset)))?;
+
+        let record_count = read_usize(reader)?;
+        let mut frame_offsets = Vec::new();
+        let mut offset = 0u64;
+        for _ in 0..record_count {
+            offset = offset
+                .checked_add(read_required_varint(reader)?)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "frame offset too large"))?;
+            frame_offsets.push(offset);
+        }
+        Ok(Self { frame_offsets })
+    }
+
+    /// The number of records in the file.
+    pub fn len(&self) -> usize {
+        self.frame_offsets.len()
+    }
+
+    /// True if the file contains no records.
+    pub fn is_empty(&self) -> bool {
+        self.frame_offsets.is_empty()
+    }
+
+    /// The byte offset of the frame of each record in the file, in the order of the records.
+    pub fn frame_offsets(&self) -> &[u64] {
+        &self.frame_offsets
+    }
+
+    /// Read the map of the record with the given index from the given file, which must be the file the index was read from.
+    pub fn read_map(&self, reader: &mut (impl Read + Seek), record: usize) -> Result<HodecoMap> {
+        let offset = *self.frame_offsets.get(record).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("no record {record} in an index of {} records", self.len()),
+            )
+        })?;
+        reader.seek(SeekFrom::Start(offset))?;
+        read_frame(reader)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "frame offset points to the end of the frames",
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hodeco::packed::{
+        read_varint, write_varint, PackedHodecoMapIndex, PackedHodecoMapReader,
+        PackedHodecoMapWriter,
+    };
+    use crate::hodeco::{Checksum, ChecksumAlgorithm, HodecoMap};
+    use std::io::{Cursor, ErrorKind};
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX.into(), u64::MAX] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value).unwrap();
+            assert_eq!(read_varint(&mut &buffer[..]).unwrap(), Some(value));
+        }
+        let mut buffer = Vec::new();
+        write_varint(&mut buffer, 127).unwrap();
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(read_varint(&mut &[][..]).unwrap(), None);
+        assert!(read_varint(&mut &[0x80][..]).is_err());
+
+        // The encoding of u64::MAX ends with 0x01, and a higher bit in the last byte overflows.
+        let mut overflowing = vec![0xFF; 9];
+        overflowing.push(0x02);
+        let error = read_varint(&mut &overflowing[..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let mut too_long = vec![0xFF; 10];
+        too_long.push(0x01);
+        assert!(read_varint(&mut &too_long[..]).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let maps = vec![
+            HodecoMap::from_run_lengths(
+                "first".to_string(),
+                vec![1, 3, 200, 1],
+                Some(Checksum {
+                    algorithm: ChecksumAlgorithm::Crc32,
+                    value: 0xDEAD_BEEF,
+                }),
+            ),
+            HodecoMap::from_run_lengths("empty".to_string(), Vec::new(), None),
+            HodecoMap::from_run_lengths(String::new(), vec![2], None),
+            HodecoMap::from_run_lengths("läst".to_string(), vec![70_000], None),
+        ];
+
+        let mut buffer = Vec::new();
+        let mut writer = PackedHodecoMapWriter::new(&mut buffer).unwrap();
+        for map in &maps {
+            writer.write(map).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let read: Vec<_> = PackedHodecoMapReader::new(&buffer[..])
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, maps);
+        assert_eq!(read[0].runs, [0, 1, 4, 204]);
+        assert_eq!(read[0].original_len, 205);
+
+        let mut file = Cursor::new(&buffer);
+        let index = PackedHodecoMapIndex::read(&mut file).unwrap();
+        assert_eq!(index.len(), maps.len());
+        assert_eq!(index.frame_offsets()[0], 8);
+        for record in [3, 0, 2, 1] {
+            assert_eq!(index.read_map(&mut file, record).unwrap(), maps[record]);
+        }
+        assert_eq!(
+            index.read_map(&mut file, 4).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let mut buffer = Vec::new();
+        PackedHodecoMapWriter::new(&mut buffer)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(PackedHodecoMapReader::new(&buffer[..]).unwrap().count(), 0);
+        let index = PackedHodecoMapIndex::read(&mut Cursor::new(&buffer)).unwrap();
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_unfinished_file() {
+        let mut buffer = Vec::new();
+        let mut writer = PackedHodecoMapWriter::new(&mut buffer).unwrap();
+        writer
+            .write(&HodecoMap::from_run_lengths(
+                "r1".to_string(),
+                vec![1, 2],
+                None,
+            ))
+            .unwrap();
+        drop(writer);
+
+        // The frames can be read, but the missing end of the frames and the missing index are detected.
+        let mut reader = PackedHodecoMapReader::new(&buffer[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        assert!(PackedHodecoMapIndex::read(&mut Cursor::new(&buffer)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(PackedHodecoMapReader::new(&b"HOCOMAP2"[..]).is_err());
+        let mut truncated = b"HOCOMAP1".to_vec();
+        truncated.extend_from_slice(&[3, b'i', b'd', 3, 1]);
+        let mut reader = PackedHodecoMapReader::new(&truncated[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+}
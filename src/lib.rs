@@ -1,7 +1,52 @@
 //! Functions to homopolymer compress arbitrary sequences.
+//!
+//! Besides the functions for single sequences, the multi-threaded compression pipeline of the binary can be configured and run on fasta files with a [`CompressionPipeline`](pipeline::CompressionPipeline):
+//!
+//! ```rust
+//! use homopolymer_compress::pipeline::CompressionPipeline;
+//!
+//! let input = b">r1\nAAACCGGGT\n>r2\nTTTA\n";
+//! let mut output = Vec::new();
+//! let stats = CompressionPipeline::new()
+//!     .threads(0)
+//!     .buffer_size(1024)
+//!     .run(&input[..], &mut output)
+//!     .unwrap();
+//! assert_eq!(stats.records, 2);
+//! assert_eq!(output, b">r1\nACGT\n>r2\nTA\n");
+//! ```
 
 #![warn(missing_docs)]
 
+pub mod analysis;
+pub mod cigar;
+pub mod concat;
+pub mod config;
+pub mod dna;
+pub mod fai;
+pub mod fasta;
+pub mod hodeco;
+pub mod minimizer;
+pub mod msa;
+pub mod parallel;
+pub mod pipeline;
+pub mod plain;
+pub mod protein;
+pub mod quality;
+pub mod signal;
+#[cfg(feature = "simd")]
+pub mod simd;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "twobit")]
+pub mod twobit;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::Hash;
+use std::iter;
+
 /// Homopolymer compress the given sequence.
 pub fn homopolymer_compress<
     'output,
@@ -28,6 +73,84 @@ pub fn homopolymer_compress<
         .flatten()
 }
 
+/// Homopolymer compress the given sequence and map each item of the output with `f`.
+///
+/// ```rust
+/// use homopolymer_compress::homopolymer_compress_map;
+///
+/// // Translate the compressed bases to 2-bit codes.
+/// let codes: Vec<u8> = homopolymer_compress_map(b"AACGGGTA".iter().copied(), |base| match base {
+///     b'A' => 0,
+///     b'C' => 1,
+///     b'G' => 2,
+///     b'T' => 3,
+///     _ => unreachable!(),
+/// })
+/// .collect();
+/// assert_eq!(codes, [0, 1, 2, 3, 0]);
+/// ```
+pub fn homopolymer_compress_map<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq + Clone,
+    Out,
+    F: 'output + FnMut(Item) -> Out,
+>(
+    input: Input,
+    f: F,
+) -> impl 'output + Iterator<Item = Out> {
+    homopolymer_compress(input).map(f)
+}
+
+/// Homopolymer compress only the runs of the given target items, and leave all other runs unchanged.
+///
+/// Runs are delimited by changes of the item as usual, so a run of a target item that is adjacent to a run of a non-target item is compressed to a single item, while the non-target run is kept as is.
+/// For example, compressing `AAACCC` with targets `{A}` results in `ACCC`.
+pub fn homopolymer_compress_only<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq + Hash + Clone,
+>(
+    input: Input,
+    targets: &'output HashSet<Item>,
+) -> impl 'output + Iterator<Item = Item> {
+    let mut compressor = HomopolymerCompressor::new();
+    input
+        .into_iter()
+        .filter(move |item| compressor.push(item.clone()).is_some() || !targets.contains(item))
+}
+
+/// An item of the input of [`homopolymer_partition`], tagged with whether homopolymer compression keeps or drops it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioned<Item> {
+    /// The first item of a run, which is part of the compressed sequence.
+    Kept(Item),
+    /// A later item of a run, which is collapsed into the first item of its run.
+    Dropped(Item),
+}
+
+/// Homopolymer compress the given sequence, but yield all items of the input tagged with whether they are kept or dropped.
+///
+/// The items are yielded in the order of the input, such that the kept items form the compressed sequence,
+/// and each run is yielded as one kept item followed by its dropped items.
+/// This allows to route kept and dropped items to different sinks in a single pass.
+pub fn homopolymer_partition<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq + Clone,
+>(
+    input: Input,
+) -> impl 'output + Iterator<Item = Partitioned<Item>> {
+    let mut compressor = HomopolymerCompressor::new();
+    input.into_iter().map(move |item| {
+        if compressor.push(item.clone()).is_some() {
+            Partitioned::Kept(item)
+        } else {
+            Partitioned::Dropped(item)
+        }
+    })
+}
+
 /// Homopolymer compress the given sequence and compute a map to homopolymer decompress the output.
 pub fn homopolymer_compress_with_hodeco_map<
     'output,
@@ -55,9 +178,634 @@ pub fn homopolymer_compress_with_hodeco_map<
         .flatten()
 }
 
+/// Homopolymer compress the given sequence and compute the length of each homopolymer run.
+///
+/// Each item of the output is a run of the input, given as the repeated item and the number of its repetitions.
+pub fn homopolymer_compress_with_run_lengths<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq,
+>(
+    input: Input,
+) -> impl 'output + Iterator<Item = (Item, usize)> {
+    let mut input = input.into_iter().peekable();
+    iter::from_fn(move || {
+        let item = input.next()?;
+        let mut length = 1;
+        while input.next_if_eq(&item).is_some() {
+            length += 1;
+        }
+        Some((item, length))
+    })
+}
+
+/// A homopolymer run is too long for the integer type its length is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunLengthOverflow {
+    /// The index of the first item of the run in the input.
+    pub start: usize,
+    /// The length of the run.
+    pub length: usize,
+}
+
+impl Display for RunLengthOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "run of length {} starting at index {} is too long",
+            self.length, self.start
+        )
+    }
+}
+
+impl Error for RunLengthOverflow {}
+
+/// Homopolymer compress the given sequence and compute the length of each run as a narrow integer, see [`homopolymer_compress_run_lengths_u16`].
+fn homopolymer_compress_run_lengths_narrow<Length: TryFrom<usize>>(
+    input: &[u8],
+) -> Result<(Vec<u8>, Vec<Length>), RunLengthOverflow> {
+    let mut compressed = Vec::new();
+    let mut lengths = Vec::new();
+    let mut start = 0;
+    for (item, length) in homopolymer_compress_with_run_lengths(input.iter().copied()) {
+        compressed.push(item);
+        lengths.push(Length::try_from(length).map_err(|_| RunLengthOverflow { start, length })?);
+        start += length;
+    }
+    Ok((compressed, lengths))
+}
+
+/// Homopolymer compress the given sequence and compute the length of each run as a `u16`, which takes a quarter of the memory of a `usize` on 64-bit platforms.
+///
+/// Runs are never split or truncated: if a run is longer than `u16::MAX`, then the first such run is returned as error.
+/// Use [`homopolymer_compress_run_lengths_u32`] if such runs are expected.
+pub fn homopolymer_compress_run_lengths_u16(
+    input: &[u8],
+) -> Result<(Vec<u8>, Vec<u16>), RunLengthOverflow> {
+    homopolymer_compress_run_lengths_narrow(input)
+}
+
+/// Homopolymer compress the given sequence and compute the length of each run as a `u32`, which takes half of the memory of a `usize` on 64-bit platforms.
+///
+/// Runs are never split or truncated: if a run is longer than `u32::MAX`, then the first such run is returned as error.
+pub fn homopolymer_compress_run_lengths_u32(
+    input: &[u8],
+) -> Result<(Vec<u8>, Vec<u32>), RunLengthOverflow> {
+    homopolymer_compress_run_lengths_narrow(input)
+}
+
+/// Homopolymer compress the given sequence and compute the prefix sums of its run lengths.
+///
+/// The prefix sums have one more element than the compressed sequence: they start at zero and end at the length of the input,
+/// such that run `i` covers the indices `sums[i]..sums[i + 1]` of the input.
+pub fn homopolymer_compress_prefix_sums<Input: IntoIterator<Item = Item>, Item: Eq + Clone>(
+    input: Input,
+) -> (Vec<Item>, Vec<usize>) {
+    let mut compressed = Vec::new();
+    let mut sums = vec![0];
+    for (item, length) in homopolymer_compress_with_run_lengths(input) {
+        compressed.push(item);
+        sums.push(sums[sums.len() - 1] + length);
+    }
+    (compressed, sums)
+}
+
+/// Homopolymer compress the given sequence and compute an arbitrary aggregate of each homopolymer run.
+///
+/// For each run, the accumulator is initialised with `init` and then `fold` is called for each item of the run,
+/// together with the index of the item in the input.
+/// Each item of the output is the first item of a run together with the accumulated value of the run.
+///
+/// ```rust
+/// use homopolymer_compress::homopolymer_compress_fold;
+///
+/// // Compute the length of each run.
+/// let runs: Vec<_> = homopolymer_compress_fold(b"AAACGG".iter().copied(), || 0, |length, _, _| length + 1).collect();
+/// assert_eq!(runs, [(b'A', 3), (b'C', 1), (b'G', 2)]);
+///
+/// // Compute the center index of each run.
+/// let centers: Vec<_> = homopolymer_compress_fold(
+///     b"AAACGG".iter().copied(),
+///     || (usize::MAX, 0),
+///     |(first, _), _, index| (first.min(index), index),
+/// )
+/// .map(|(item, (first, last))| (item, (first + last) / 2))
+/// .collect();
+/// assert_eq!(centers, [(b'A', 1), (b'C', 3), (b'G', 4)]);
+/// ```
+pub fn homopolymer_compress_fold<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq + Clone,
+    Accumulator: 'output,
+    Fold: 'output + FnMut(Accumulator, &Item, usize) -> Accumulator,
+>(
+    input: Input,
+    init: impl 'output + Fn() -> Accumulator,
+    mut fold: Fold,
+) -> impl 'output + Iterator<Item = (Item, Accumulator)> {
+    let mut input = input.into_iter().enumerate().peekable();
+    iter::from_fn(move || {
+        let (index, item) = input.next()?;
+        let mut accumulator = fold(init(), &item, index);
+        while let Some((index, next_item)) = input.next_if(|(_, next_item)| next_item == &item) {
+            accumulator = fold(accumulator, &next_item, index);
+        }
+        Some((item, accumulator))
+    })
+}
+
+/// Homopolymer compress the given sequence and compute the parity of the index at which each run starts in the input.
+///
+/// Each item of the output is the first item of a run together with `true` if the run starts at an even index, and `false` otherwise.
+pub fn homopolymer_compress_with_parity<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq + Clone,
+>(
+    input: Input,
+) -> impl 'output + Iterator<Item = (Item, bool)> {
+    homopolymer_compress_fold(input, || usize::MAX, |start, _, index| start.min(index))
+        .map(|(item, start)| (item, start % 2 == 0))
+}
+
+/// Expand the given homopolymer runs into the original sequence.
+///
+/// Each run is given as the repeated item and the number of its repetitions, as produced by [`homopolymer_compress_with_run_lengths`].
+/// Runs with zero repetitions are skipped.
+pub fn homopolymer_expand<
+    'output,
+    Runs: 'output + IntoIterator<Item = (Item, usize)>,
+    Item: 'output + Clone,
+>(
+    runs: Runs,
+) -> impl 'output + Iterator<Item = Item> {
+    runs.into_iter()
+        .flat_map(|(item, length)| iter::repeat(item).take(length))
+}
+
+/// Split the given sequence into its homopolymer runs without copying.
+///
+/// Each item of the output is the subslice of the input covering one whole run,
+/// such that its first item is the item of the compressed sequence and its length is the length of the run.
+pub fn homopolymer_run_slices<Item: Eq>(input: &[Item]) -> impl '_ + Iterator<Item = &[Item]> {
+    let mut remaining = input;
+    iter::from_fn(move || {
+        let first = remaining.first()?;
+        let length = remaining
+            .iter()
+            .position(|item| item != first)
+            .unwrap_or(remaining.len());
+        let (run, rest) = remaining.split_at(length);
+        remaining = rest;
+        Some(run)
+    })
+}
+
+/// Split the given sequence into its homopolymer runs without copying, yielding the leader of each run together with the whole run.
+///
+/// The leader is a reference to the first item of the run, i.e. the item of the compressed sequence.
+/// The run is the subslice of the input covering the whole run as in [`homopolymer_run_slices`], such that its length is the length of the run.
+pub fn homopolymer_runs_with_slices<Item: Eq>(
+    input: &[Item],
+) -> impl '_ + Iterator<Item = (&Item, &[Item])> {
+    homopolymer_run_slices(input).map(|run| (&run[0], run))
+}
+
+/// Homopolymer compress the given sequence and aggregate a parallel array of annotations per run.
+///
+/// The `parallel` array has one annotation per item of `seq`, e.g. a quality score or a read depth.
+/// For each run, `aggregate` is called with the annotations of all items of the run, and its result becomes the annotation of the compressed item.
+/// For example, [`QualityMerge::aggregate`](crate::quality::QualityMerge::aggregate) aggregates the quality scores of a fastq record.
+///
+/// Returns the compressed sequence and the aggregated annotations, which have equal length.
+/// Panics if `seq` and `parallel` have different lengths.
+pub fn homopolymer_compress_with_parallel<Item: Eq + Clone, T, A: Fn(&[T]) -> T>(
+    seq: &[Item],
+    parallel: &[T],
+    aggregate: A,
+) -> (Vec<Item>, Vec<T>) {
+    assert_eq!(
+        seq.len(),
+        parallel.len(),
+        "the sequence and the parallel array must have equal lengths"
+    );
+    let mut start = 0;
+    homopolymer_run_slices(seq)
+        .map(|run| {
+            let end = start + run.len();
+            let annotation = aggregate(&parallel[start..end]);
+            start = end;
+            (run[0].clone(), annotation)
+        })
+        .unzip()
+}
+
+/// Find the longest homopolymer run of the given sequence in a single pass.
+///
+/// Returns the repeated item, the length of the run and the index of its first item in the sequence, or `None` if the sequence is empty.
+/// If there are multiple longest runs, the first one is returned.
+pub fn longest_run<Input: IntoIterator<Item = Item>, Item: Eq>(
+    input: Input,
+) -> Option<(Item, usize, usize)> {
+    let mut longest: Option<(Item, usize, usize)> = None;
+    let mut start = 0;
+    for (item, length) in homopolymer_compress_with_run_lengths(input) {
+        if longest
+            .as_ref()
+            .map(|(_, longest_length, _)| length > *longest_length)
+            .unwrap_or(true)
+        {
+            longest = Some((item, length, start));
+        }
+        start += length;
+    }
+    longest
+}
+
+/// Homopolymer compress the given sequence and yield each pair of consecutive items of the compressed sequence.
+///
+/// Since consecutive runs have different items, the two items of each pair are always different.
+/// For example, `AAACCT` yields `(A, C)` and `(C, T)`.
+/// If the sequence has fewer than two runs, nothing is yielded.
+pub fn homopolymer_transitions<
+    'output,
+    Input: 'output + IntoIterator<Item = Item>,
+    Item: 'output + Eq + Clone,
+>(
+    input: Input,
+) -> impl 'output + Iterator<Item = (Item, Item)> {
+    let mut previous = None;
+    homopolymer_compress(input).filter_map(move |item| {
+        previous
+            .replace(item.clone())
+            .map(|previous| (previous, item))
+    })
+}
+
+/// Replace each homopolymer run of the given sequence with exactly `target_len` copies of its symbol.
+///
+/// With a `target_len` of one, this is equal to [`homopolymer_compress`].
+/// Longer runs are shortened and shorter runs are lengthened, e.g. with a `target_len` of three, both `AA` and `AAAAA` become `AAA`.
+/// With a `target_len` of zero, the output is empty.
+pub fn homopolymer_normalize(input: &[u8], target_len: usize) -> Vec<u8> {
+    homopolymer_expand(
+        homopolymer_compress_with_run_lengths(input.iter().copied())
+            .map(|(item, _)| (item, target_len)),
+    )
+    .collect()
+}
+
+/// Homopolymer compress only the runs of the given sequence that have at least `min_run` items, and shorten them to at most `max_run` items.
+///
+/// The thresholds are applied in this order: runs shorter than `min_run` are kept unchanged, and all other runs are replaced by `max_run` copies of their symbol, or by the whole run if it is shorter than that.
+/// For example, with a `min_run` of three and a `max_run` of two, `AA` stays `AA`, while `AAA` and `AAAAA` both become `AA`.
+/// With a `min_run` of at most one and a `max_run` of one, this is equal to [`homopolymer_compress`].
+///
+/// Panics if `max_run` is zero.
+pub fn homopolymer_compress_clamp(input: &[u8], min_run: usize, max_run: usize) -> Vec<u8> {
+    assert!(max_run > 0, "max_run must be positive");
+    homopolymer_expand(
+        homopolymer_compress_with_run_lengths(input.iter().copied()).map(|(item, length)| {
+            if length < min_run {
+                (item, length)
+            } else {
+                (item, length.min(max_run))
+            }
+        }),
+    )
+    .collect()
+}
+
+/// Homopolymer compress the given sequence, but keep the first and the last run at their exact lengths.
+///
+/// This allows to process a sequence in chunks that are stitched together later, since the runs at the chunk boundaries may continue in the neighbouring chunks.
+/// All interior runs are compressed to a single item as in [`homopolymer_compress`].
+/// For example, `AAACCGGTTT` becomes `AAACGTTT`.
+///
+/// A sequence with a single run is kept unchanged, since this run is both the first and the last run.
+/// Likewise, a sequence with two runs has no interior runs, and is kept unchanged as well.
+pub fn homopolymer_compress_keep_ends(input: &[u8]) -> Vec<u8> {
+    let mut runs = homopolymer_run_slices(input).peekable();
+    let mut compressed = Vec::new();
+    if let Some(first) = runs.next() {
+        compressed.extend_from_slice(first);
+    }
+    while let Some(run) = runs.next() {
+        if runs.peek().is_some() {
+            compressed.push(run[0]);
+        } else {
+            compressed.extend_from_slice(run);
+        }
+    }
+    compressed
+}
+
+/// Compress tandem repeats of the given period to a single copy of their unit, generalising homopolymer compression to short tandem repeats.
+///
+/// The sequence is scanned from left to right, and at each position the next `period` items are the unit of a tandem repeat.
+/// The repeat extends as far as each item equals the item `period` positions before it, and is replaced by a single copy of its unit.
+/// Hence a partial trailing unit is part of the repeat and is removed together with all full copies but the first.
+/// For example, with a period of two, `ATATAT` and `ATATA` both become `AT`, `AAA` becomes `AA`, and `ACGT` stays `ACGT`.
+/// If fewer than `period` items remain at the end of the sequence, they are kept unchanged.
+///
+/// Repeats are found greedily, so e.g. `CATATAT` becomes `CATA`, since the first unit is `CA`, and the repeat of `TA` starts after it.
+/// With a period of one, this is equal to [`homopolymer_compress`].
+///
+/// Panics if `period` is zero.
+pub fn homopolymer_compress_period(input: &[u8], period: usize) -> Vec<u8> {
+    assert!(period > 0, "period must be positive");
+    let mut compressed = Vec::new();
+    let mut start = 0;
+    while start + period <= input.len() {
+        let end = (start + period..input.len())
+            .find(|&index| input[index] != input[index - period])
+            .unwrap_or(input.len());
+        compressed.extend_from_slice(&input[start..start + period]);
+        start = end;
+    }
+    compressed.extend_from_slice(&input[start..]);
+    compressed
+}
+
+/// Merge adjacent homopolymer runs of equal items into a single run, summing their lengths.
+///
+/// This is homopolymer compression on sequences that are already given as runs, e.g. after concatenating the runs of multiple compressed segments.
+pub fn coalesce_runs<
+    'output,
+    Runs: 'output + IntoIterator<Item = (Item, usize)>,
+    Item: 'output + Eq,
+>(
+    runs: Runs,
+) -> impl 'output + Iterator<Item = (Item, usize)> {
+    let mut runs = runs.into_iter().peekable();
+    iter::from_fn(move || {
+        let (item, mut length) = runs.next()?;
+        while let Some((_, next_length)) = runs.next_if(|(next_item, _)| next_item == &item) {
+            length += next_length;
+        }
+        Some((item, length))
+    })
+}
+
+/// Merge the compressions of two overlapping windows of a sequence, where the last `overlap` items of the first window are the first `overlap` items of the second window.
+///
+/// The windows are given as runs, as produced by [`homopolymer_compress_with_run_lengths`], since the overlap is counted in items of the original sequence.
+/// The first window is kept whole, and the runs of the second window are trimmed by `overlap` items from the start, which may shorten its first remaining run.
+/// If the last run of the first window has the same item as the first remaining run of the second window, then both are merged into one run at the seam.
+/// For example, the windows `AACC` and `CCGG` with an overlap of two are merged to the runs of `AACCGG`,
+/// and the windows `AACC` and `CCCG` with an overlap of two are merged to the runs of `AACCCG`.
+///
+/// Panics if `overlap` is greater than the length of the second window.
+pub fn merge_overlapping_compressed<Item: Eq + Clone>(
+    a: &[(Item, usize)],
+    b: &[(Item, usize)],
+    overlap: usize,
+) -> Vec<(Item, usize)> {
+    let mut remaining_overlap = overlap;
+    let mut b_runs = Vec::new();
+    for (item, length) in b {
+        let dropped = remaining_overlap.min(*length);
+        remaining_overlap -= dropped;
+        if *length > dropped {
+            b_runs.push((item.clone(), length - dropped));
+        }
+    }
+    assert_eq!(
+        remaining_overlap, 0,
+        "overlap must not be greater than the second window"
+    );
+    coalesce_runs(a.iter().cloned().chain(b_runs)).collect()
+}
+
+/// Homopolymer compress the given sequence and compute a mask of the positions of the original sequence that are kept.
+///
+/// The mask has one bit per item of the original sequence, which is set if the item is the first of its run, i.e. if it is part of the compressed sequence.
+/// Hence, the number of set bits in the mask equals the length of the compressed sequence.
+#[cfg(feature = "bitvec")]
+pub fn homopolymer_compress_mask<Input: IntoIterator<Item = Item>, Item: Eq + Clone>(
+    input: Input,
+) -> (Vec<Item>, bitvec::vec::BitVec) {
+    let mut compressor = HomopolymerCompressor::new();
+    let mut compressed = Vec::new();
+    let mut mask = bitvec::vec::BitVec::new();
+    for item in input {
+        if let Some(item) = compressor.push(item) {
+            compressed.push(item);
+            mask.push(true);
+        } else {
+            mask.push(false);
+        }
+    }
+    (compressed, mask)
+}
+
+/// Homopolymer compress the given text by its extended grapheme clusters, i.e. the characters as perceived by a user.
+///
+/// Each item of the output is the first cluster of a run of identical clusters, as a subslice of the input.
+/// Unlike compressing the `char`s of the text, this never splits a cluster that consists of multiple code points, e.g. an emoji with a skin tone modifier.
+#[cfg(feature = "unicode")]
+pub fn homopolymer_compress_graphemes(input: &str) -> impl '_ + Iterator<Item = &str> {
+    homopolymer_compress(unicode_segmentation::UnicodeSegmentation::graphemes(
+        input, true,
+    ))
+}
+
+/// Collect the homopolymer compression of a sequence into a vector.
+///
+/// ```
+/// use homopolymer_compress::HomopolymerCompressCollect;
+///
+/// let reads = vec![b"AAC".to_vec(), b"CCGT".to_vec()];
+/// let compressed = reads.into_iter().flatten().homopolymer_compressed_vec();
+/// assert_eq!(compressed, b"ACGT");
+/// ```
+pub trait HomopolymerCompressCollect: IntoIterator {
+    /// Homopolymer compress this sequence and collect the result into a vector.
+    fn homopolymer_compressed_vec(self) -> Vec<Self::Item>;
+}
+
+impl<Input: IntoIterator> HomopolymerCompressCollect for Input
+where
+    Input::Item: Eq + Clone,
+{
+    fn homopolymer_compressed_vec(self) -> Vec<Self::Item> {
+        homopolymer_compress(self).collect()
+    }
+}
+
+/// The minimum length of the homopolymer compression of a sequence of length `input_len`.
+///
+/// A non-empty sequence always compresses to at least one item, and the empty sequence compresses to the empty sequence.
+///
+/// ```
+/// use homopolymer_compress::min_compressed_len;
+///
+/// const MIN_LEN: usize = min_compressed_len(100);
+/// assert_eq!(MIN_LEN, 1);
+/// assert_eq!(min_compressed_len(0), 0);
+/// ```
+pub const fn min_compressed_len(input_len: usize) -> usize {
+    if input_len == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+/// The maximum length of the homopolymer compression of a sequence of length `input_len`.
+///
+/// Homopolymer compression never makes a sequence longer, and a sequence without repeated adjacent items stays unchanged.
+///
+/// ```
+/// use homopolymer_compress::{homopolymer_compress, max_compressed_len};
+///
+/// const INPUT: &[u8] = b"AACGTTT";
+/// let mut buffer = [0u8; max_compressed_len(INPUT.len())];
+/// let mut len = 0;
+/// for (target, item) in buffer.iter_mut().zip(homopolymer_compress(INPUT.iter().copied())) {
+///     *target = item;
+///     len += 1;
+/// }
+/// assert_eq!(&buffer[..len], b"ACGT");
+/// ```
+pub const fn max_compressed_len(input_len: usize) -> usize {
+    input_len
+}
+
+/// A stateful homopolymer compressor that receives its input in pieces.
+///
+/// The compressor remembers the last item it has seen, such that a homopolymer that is split between two pieces is still compressed into a single item.
+#[derive(Debug, Clone)]
+pub struct HomopolymerCompressor<Item> {
+    previous_item: Option<Item>,
+}
+
+impl<Item: Eq + Clone> HomopolymerCompressor<Item> {
+    /// Create a new compressor that has not seen any items yet.
+    /// The first item it receives always starts a new homopolymer, such that its input is compressed independently of any other sequence.
+    pub fn new() -> Self {
+        Self {
+            previous_item: None,
+        }
+    }
+
+    /// Create a new compressor that continues after the given item, e.g. the last item of the previous record of a sequence that was split into multiple records.
+    /// If the input starts with a homopolymer of this item, then the homopolymer is treated as a continuation and removed completely.
+    pub fn with_previous(previous_item: Item) -> Self {
+        Self {
+            previous_item: Some(previous_item),
+        }
+    }
+
+    /// Feed the next item into the compressor.
+    /// Returns the item if it starts a new homopolymer, and `None` if it continues the previous one.
+    pub fn push(&mut self, item: Item) -> Option<Item> {
+        if self.previous_item.as_ref() == Some(&item) {
+            None
+        } else {
+            self.previous_item = Some(item.clone());
+            Some(item)
+        }
+    }
+
+    /// Homopolymer compress the given piece of the sequence, continuing from the previous pieces.
+    pub fn compress<'compressor, Input: 'compressor + IntoIterator<Item = Item>>(
+        &'compressor mut self,
+        input: Input,
+    ) -> impl 'compressor + Iterator<Item = Item> {
+        input.into_iter().filter_map(move |item| self.push(item))
+    }
+}
+
+impl<Item: Eq + Clone> Default for HomopolymerCompressor<Item> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A homopolymer compressed sequence together with the length of each of its runs.
+///
+/// It is displayed compactly as each item followed by the length of its run, where items are written as characters:
+///
+/// ```rust
+/// use homopolymer_compress::RunLengthEncoded;
+///
+/// let encoded: RunLengthEncoded<u8> = b"AAACTT".iter().copied().collect();
+/// assert_eq!(encoded.to_string(), "A3C1T2");
+/// assert_eq!(format!("{encoded:?}"), "RunLengthEncoded(A3C1T2)");
+/// ```
+///
+/// Since lengths are written in decimal, this notation is ambiguous if the items themselves are digits.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct RunLengthEncoded<Item> {
+    runs: Vec<(Item, usize)>,
+}
+
+impl<Item: Eq> RunLengthEncoded<Item> {
+    /// Create an encoded sequence from the given runs, given as the repeated item and the number of its repetitions.
+    ///
+    /// Adjacent runs of equal items are merged as in [`coalesce_runs`], and runs with zero repetitions are dropped,
+    /// such that equal sequences always have equal encodings.
+    pub fn from_runs(runs: impl IntoIterator<Item = (Item, usize)>) -> Self {
+        Self {
+            runs: coalesce_runs(runs.into_iter().filter(|(_, length)| *length > 0)).collect(),
+        }
+    }
+}
+
+impl<Item> RunLengthEncoded<Item> {
+    /// The runs of the sequence, given as the repeated item and the number of its repetitions.
+    pub fn runs(&self) -> &[(Item, usize)] {
+        &self.runs
+    }
+
+    /// Expand the runs into the original sequence.
+    pub fn expand(&self) -> impl '_ + Iterator<Item = Item>
+    where
+        Item: Clone,
+    {
+        homopolymer_expand(self.runs.iter().cloned())
+    }
+}
+
+impl<Item: Eq> FromIterator<Item> for RunLengthEncoded<Item> {
+    fn from_iter<Input: IntoIterator<Item = Item>>(input: Input) -> Self {
+        Self {
+            runs: homopolymer_compress_with_run_lengths(input).collect(),
+        }
+    }
+}
+
+impl<Item: Clone + Into<char>> Display for RunLengthEncoded<Item> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (item, length) in &self.runs {
+            write!(f, "{}{length}", item.clone().into())?;
+        }
+        Ok(())
+    }
+}
+
+impl<Item: Clone + Into<char>> Debug for RunLengthEncoded<Item> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RunLengthEncoded({self})")
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{homopolymer_compress, homopolymer_compress_with_hodeco_map};
+    use crate::{
+        coalesce_runs, homopolymer_compress, homopolymer_compress_clamp, homopolymer_compress_fold,
+        homopolymer_compress_keep_ends, homopolymer_compress_only, homopolymer_compress_period,
+        homopolymer_compress_prefix_sums, homopolymer_compress_run_lengths_u16,
+        homopolymer_compress_run_lengths_u32, homopolymer_compress_with_hodeco_map,
+        homopolymer_compress_with_parallel, homopolymer_compress_with_parity,
+        homopolymer_compress_with_run_lengths, homopolymer_expand, homopolymer_normalize,
+        homopolymer_partition, homopolymer_run_slices, homopolymer_runs_with_slices,
+        homopolymer_transitions, longest_run, merge_overlapping_compressed, HomopolymerCompressor,
+        Partitioned, RunLengthEncoded, RunLengthOverflow,
+    };
+    use std::collections::HashSet;
     use std::iter;
 
     #[test]
@@ -68,6 +816,23 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_homopolymer_compression_only() {
+        let targets: HashSet<_> = [b'A'].into_iter().collect();
+        let compress = |input: &[u8]| -> Vec<_> {
+            homopolymer_compress_only(input.iter().cloned(), &targets).collect()
+        };
+        assert_eq!(compress(b"AAACCC"), b"ACCC");
+        assert_eq!(compress(b"CCAAAGGAT"), b"CCAGGAT");
+        assert_eq!(compress(b"CCGGT"), b"CCGGT");
+        assert_eq!(compress(b""), b"");
+
+        let targets: HashSet<_> = [b'A', b'T'].into_iter().collect();
+        let actual: Vec<_> =
+            homopolymer_compress_only(b"AATTTCCAA".iter().cloned(), &targets).collect();
+        assert_eq!(actual, b"ATCCA");
+    }
+
     #[test]
     fn test_hodeco_mapping() {
         let input = b"ACAARRRTGGGTGTJASAAAI";
@@ -84,4 +849,427 @@ mod tests {
             .collect();
         assert_eq!(hodeco, input);
     }
+
+    #[test]
+    fn test_hodeco_mapping_empty() {
+        let (actual, mut hodeco_mapping): (Vec<_>, Vec<_>) =
+            homopolymer_compress_with_hodeco_map(iter::empty::<u8>()).unzip();
+        hodeco_mapping.push(0);
+        assert!(actual.is_empty());
+        assert_eq!(hodeco_mapping, [0]);
+        assert_eq!(hodeco_mapping.windows(2).count(), 0);
+    }
+
+    #[test]
+    fn test_run_lengths() {
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        let (actual, run_lengths): (Vec<_>, Vec<_>) =
+            homopolymer_compress_with_run_lengths(input.iter().cloned()).unzip();
+        assert_eq!(actual, b"ACARTGTGTJASAI");
+        assert_eq!(run_lengths, [1, 1, 2, 3, 1, 3, 1, 1, 1, 1, 1, 1, 3, 1]);
+        assert_eq!(
+            homopolymer_compress_with_run_lengths(iter::empty::<u8>()).next(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        let expanded: Vec<_> =
+            homopolymer_expand(homopolymer_compress_with_run_lengths(input.iter().cloned()))
+                .collect();
+        assert_eq!(expanded, input);
+
+        let expanded: Vec<_> = homopolymer_expand([(b'A', 2), (b'C', 0), (b'G', 1)]).collect();
+        assert_eq!(expanded, b"AAG");
+    }
+
+    #[test]
+    fn test_fold() {
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        let runs: Vec<_> =
+            homopolymer_compress_fold(input.iter().cloned(), || 0, |length, _, _| length + 1)
+                .collect();
+        let expected: Vec<_> =
+            homopolymer_compress_with_run_lengths(input.iter().cloned()).collect();
+        assert_eq!(runs, expected);
+
+        let indices: Vec<_> = homopolymer_compress_fold(
+            b"AACA".iter().cloned(),
+            Vec::new,
+            |mut indices, _, index| {
+                indices.push(index);
+                indices
+            },
+        )
+        .collect();
+        assert_eq!(
+            indices,
+            [(b'A', vec![0, 1]), (b'C', vec![2]), (b'A', vec![3])]
+        );
+    }
+
+    #[test]
+    fn test_parity() {
+        let runs: Vec<_> = homopolymer_compress_with_parity(b"AACGGGTA".iter().copied()).collect();
+        assert_eq!(
+            runs,
+            [
+                (b'A', true),
+                (b'C', true),
+                (b'G', false),
+                (b'T', true),
+                (b'A', false)
+            ]
+        );
+        assert_eq!(
+            homopolymer_compress_with_parity(iter::empty::<u8>()).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_prefix_sums() {
+        for input in [&b"AACGGGTA"[..], b"A", b""] {
+            let (compressed, sums) = homopolymer_compress_prefix_sums(input.iter().copied());
+            assert_eq!(sums.len(), compressed.len() + 1);
+            assert!(sums[0] == 0 && sums[sums.len() - 1] == input.len());
+            for (index, &item) in compressed.iter().enumerate() {
+                assert!(input[sums[index]..sums[index + 1]]
+                    .iter()
+                    .all(|&other| other == item));
+            }
+        }
+        assert_eq!(
+            homopolymer_compress_prefix_sums(b"AACGGGTA".iter().copied()).1,
+            [0, 2, 3, 6, 7, 8]
+        );
+    }
+
+    #[test]
+    fn test_run_slices() {
+        let input = b"AACGGGTA";
+        let runs: Vec<_> = homopolymer_run_slices(input).collect();
+        assert_eq!(runs, [&b"AA"[..], b"C", b"GGG", b"T", b"A"]);
+        assert_eq!(runs.concat(), input);
+        assert_eq!(homopolymer_run_slices::<u8>(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_run_lengths_narrow() {
+        assert_eq!(
+            homopolymer_compress_run_lengths_u16(b"AACGGGT"),
+            Ok((b"ACGT".to_vec(), vec![2, 1, 3, 1]))
+        );
+
+        let mut input = b"AC".to_vec();
+        input.extend(iter::repeat(b'G').take(usize::from(u16::MAX)));
+        let (compressed, lengths) = homopolymer_compress_run_lengths_u16(&input).unwrap();
+        assert_eq!(compressed, b"ACG");
+        assert_eq!(lengths[2], u16::MAX);
+
+        input.extend(b"GT");
+        assert_eq!(
+            homopolymer_compress_run_lengths_u16(&input),
+            Err(RunLengthOverflow {
+                start: 2,
+                length: usize::from(u16::MAX) + 1
+            })
+        );
+        let (compressed, lengths) = homopolymer_compress_run_lengths_u32(&input).unwrap();
+        assert_eq!(compressed, b"ACGT");
+        assert_eq!(lengths, [1, 1, u32::from(u16::MAX) + 1, 1]);
+    }
+
+    #[test]
+    fn test_partition() {
+        let input = b"AACGGGTAA";
+        let partition: Vec<_> = homopolymer_partition(input.iter().copied()).collect();
+        assert_eq!(partition.len(), input.len());
+        let kept: Vec<_> = partition
+            .iter()
+            .filter_map(|item| match item {
+                Partitioned::Kept(item) => Some(*item),
+                Partitioned::Dropped(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            kept,
+            homopolymer_compress(input.iter().copied()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            partition[..4],
+            [
+                Partitioned::Kept(b'A'),
+                Partitioned::Dropped(b'A'),
+                Partitioned::Kept(b'C'),
+                Partitioned::Kept(b'G')
+            ]
+        );
+        assert_eq!(homopolymer_partition(iter::empty::<u8>()).count(), 0);
+    }
+
+    #[test]
+    fn test_runs_with_slices() {
+        let input = b"AACGGGTAA";
+        let mut end = 0;
+        for (leader, run) in homopolymer_runs_with_slices(input) {
+            // Each run starts exactly where the previous one ended, so the runs tile the input.
+            assert_eq!(run.as_ptr(), input[end..].as_ptr());
+            assert!(std::ptr::eq(leader, &run[0]));
+            assert!(run.iter().all(|item| item == leader));
+            end += run.len();
+        }
+        assert_eq!(end, input.len());
+        let leaders: Vec<_> = homopolymer_runs_with_slices(input)
+            .map(|(&leader, _)| leader)
+            .collect();
+        assert_eq!(leaders, b"ACGTA");
+        assert_eq!(homopolymer_runs_with_slices::<u8>(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_with_parallel() {
+        let depths = [1, 2, 3, 4, 5, 6, 7, 8];
+        let sum = |run: &[usize]| run.iter().sum();
+        let (compressed, sums) = homopolymer_compress_with_parallel(b"AACGGGTA", &depths, sum);
+        assert_eq!(compressed, b"ACGTA");
+        assert_eq!(sums, [3, 3, 15, 7, 8]);
+        let (compressed, sums) = homopolymer_compress_with_parallel::<u8, usize, _>(&[], &[], sum);
+        assert!(compressed.is_empty() && sums.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "equal lengths")]
+    fn test_with_parallel_length_mismatch() {
+        homopolymer_compress_with_parallel(b"AAC", &[1, 2], |run: &[usize]| run[0]);
+    }
+
+    #[test]
+    fn test_run_length_encoded() {
+        let encoded: RunLengthEncoded<u8> = b"AAACTT".iter().copied().collect();
+        assert_eq!(encoded.runs(), [(b'A', 3), (b'C', 1), (b'T', 2)]);
+        assert_eq!(encoded.expand().collect::<Vec<_>>(), b"AAACTT");
+        assert_eq!(
+            RunLengthEncoded::from_runs([(b'A', 2), (b'A', 1), (b'G', 0), (b'C', 1), (b'T', 2)]),
+            encoded
+        );
+        assert_eq!(format!("{encoded:?}"), "RunLengthEncoded(A3C1T2)");
+
+        let encoded: RunLengthEncoded<char> = "ääöx".chars().collect();
+        assert_eq!(encoded.to_string(), "ä2ö1x1");
+        assert_eq!(RunLengthEncoded::<char>::default().to_string(), "");
+    }
+
+    #[test]
+    fn test_longest_run() {
+        assert_eq!(
+            longest_run(b"ACCGGGTTAAA".iter().copied()),
+            Some((b'G', 3, 3))
+        );
+        assert_eq!(longest_run(b"A".iter().copied()), Some((b'A', 1, 0)));
+        assert_eq!(longest_run(iter::empty::<u8>()), None);
+    }
+
+    #[test]
+    fn test_transitions() {
+        let transitions: Vec<_> = homopolymer_transitions(b"AAACCT".iter().copied()).collect();
+        assert_eq!(transitions, [(b'A', b'C'), (b'C', b'T')]);
+        assert_eq!(homopolymer_transitions(b"AAAA".iter().copied()).count(), 0);
+        assert_eq!(homopolymer_transitions(iter::empty::<u8>()).count(), 0);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(homopolymer_normalize(b"AACAAAAAT", 3), b"AAACCCAAATTT");
+        assert_eq!(
+            homopolymer_normalize(b"ACAARRRTGGGTGTJASAAAI", 1),
+            homopolymer_compress(b"ACAARRRTGGGTGTJASAAAI".iter().copied()).collect::<Vec<_>>()
+        );
+        assert_eq!(homopolymer_normalize(b"AACT", 0), b"");
+        assert_eq!(homopolymer_normalize(b"", 2), b"");
+    }
+
+    #[test]
+    fn test_clamp() {
+        // Below both thresholds, between them, and above both.
+        assert_eq!(homopolymer_compress_clamp(b"AA", 3, 2), b"AA");
+        assert_eq!(homopolymer_compress_clamp(b"AAA", 3, 2), b"AA");
+        assert_eq!(homopolymer_compress_clamp(b"AAAAA", 3, 2), b"AA");
+        // A run that reaches `min_run` but is already shorter than `max_run` is kept.
+        assert_eq!(homopolymer_compress_clamp(b"AAA", 2, 4), b"AAA");
+        assert_eq!(homopolymer_compress_clamp(b"CAAAAAGGT", 2, 4), b"CAAAAGGT");
+        assert_eq!(homopolymer_compress_clamp(b"CCAAAAAGT", 3, 1), b"CCAGT");
+        assert_eq!(
+            homopolymer_compress_clamp(b"ACAARRRTGGGTGTJASAAAI", 0, 1),
+            homopolymer_compress(b"ACAARRRTGGGTGTJASAAAI".iter().copied()).collect::<Vec<_>>()
+        );
+        assert_eq!(homopolymer_compress_clamp(b"", 2, 2), b"");
+    }
+
+    #[test]
+    fn test_compress_period() {
+        assert_eq!(homopolymer_compress_period(b"ATATAT", 2), b"AT");
+        assert_eq!(homopolymer_compress_period(b"AAA", 2), b"AA");
+        assert_eq!(homopolymer_compress_period(b"ACGT", 2), b"ACGT");
+        assert_eq!(homopolymer_compress_period(b"GGATATATCC", 2), b"GGATCC");
+        assert_eq!(homopolymer_compress_period(b"CAGCAGCAGT", 3), b"CAGT");
+        // A partial trailing unit is part of the repeat.
+        assert_eq!(homopolymer_compress_period(b"ATATA", 2), b"AT");
+        assert_eq!(homopolymer_compress_period(b"CAGCAGCAT", 3), b"CAGT");
+        // Fewer than `period` remaining items are kept.
+        assert_eq!(homopolymer_compress_period(b"AAT", 2), b"AAT");
+        assert_eq!(homopolymer_compress_period(b"A", 2), b"A");
+        assert_eq!(homopolymer_compress_period(b"", 2), b"");
+        // Repeats are found greedily.
+        assert_eq!(homopolymer_compress_period(b"CATATAT", 2), b"CATA");
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        assert_eq!(
+            homopolymer_compress_period(input, 1),
+            homopolymer_compress(input.iter().copied()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "period must be positive")]
+    fn test_compress_period_zero() {
+        homopolymer_compress_period(b"ACGT", 0);
+    }
+
+    #[test]
+    fn test_keep_ends() {
+        assert_eq!(homopolymer_compress_keep_ends(b"AAACCGGTTT"), b"AAACGTTT");
+        assert_eq!(homopolymer_compress_keep_ends(b"AACCCCGGT"), b"AACGT");
+        // A single run is both the first and the last run.
+        assert_eq!(homopolymer_compress_keep_ends(b"AAAA"), b"AAAA");
+        // Two runs have no interior runs.
+        assert_eq!(homopolymer_compress_keep_ends(b"AAACC"), b"AAACC");
+        // Three runs have a single interior run.
+        assert_eq!(homopolymer_compress_keep_ends(b"AACCCGG"), b"AACGG");
+        assert_eq!(homopolymer_compress_keep_ends(b"ACG"), b"ACG");
+        assert_eq!(homopolymer_compress_keep_ends(b""), b"");
+    }
+
+    #[test]
+    fn test_coalesce_runs() {
+        let coalesced: Vec<_> = coalesce_runs([(b'A', 2), (b'A', 3), (b'C', 1)]).collect();
+        assert_eq!(coalesced, [(b'A', 5), (b'C', 1)]);
+
+        let coalesced: Vec<_> =
+            coalesce_runs([(b'A', 1), (b'C', 2), (b'C', 1), (b'C', 4), (b'A', 1)]).collect();
+        assert_eq!(coalesced, [(b'A', 1), (b'C', 7), (b'A', 1)]);
+        assert_eq!(coalesce_runs(Vec::<(u8, usize)>::new()).count(), 0);
+    }
+
+    #[test]
+    fn test_merge_overlapping_compressed() {
+        let runs = |sequence: &[u8]| -> Vec<_> {
+            homopolymer_compress_with_run_lengths(sequence.iter().copied()).collect()
+        };
+        // The seam run continues into the second window and is collapsed.
+        assert_eq!(
+            merge_overlapping_compressed(&runs(b"AACC"), &runs(b"CCCG"), 2),
+            runs(b"AACCCG")
+        );
+        assert_eq!(
+            merge_overlapping_compressed(&runs(b"ACGTT"), &runs(b"GTTTTAC"), 3),
+            runs(b"ACGTTTTAC")
+        );
+        // The seam is between two different runs.
+        assert_eq!(
+            merge_overlapping_compressed(&runs(b"AACC"), &runs(b"CCGG"), 2),
+            runs(b"AACCGG")
+        );
+        assert_eq!(
+            merge_overlapping_compressed(&runs(b"AACC"), &runs(b"GT"), 0),
+            runs(b"AACCGT")
+        );
+        assert_eq!(
+            merge_overlapping_compressed(&runs(b"AACC"), &runs(b"CC"), 2),
+            runs(b"AACC")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap must not be greater than the second window")]
+    fn test_merge_overlapping_compressed_too_long_overlap() {
+        merge_overlapping_compressed(&[(b'A', 2)], &[(b'A', 2)], 3);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_graphemes() {
+        use crate::homopolymer_compress_graphemes;
+
+        // Thumbs up with medium and with dark skin tone, each consisting of two code points.
+        let input = "👍🏽👍🏽👍🏿a";
+        let compressed: Vec<_> = homopolymer_compress_graphemes(input).collect();
+        assert_eq!(compressed, ["👍🏽", "👍🏿", "a"]);
+        // Compressing by code point does not see the repetition.
+        assert_eq!(
+            homopolymer_compress(input.chars()).count(),
+            input.chars().count()
+        );
+
+        // An e with a combining acute accent.
+        let compressed: String = homopolymer_compress_graphemes("e\u{301}e\u{301}ee").collect();
+        assert_eq!(compressed, "e\u{301}e");
+        assert_eq!(homopolymer_compress_graphemes("").count(), 0);
+    }
+
+    #[cfg(feature = "bitvec")]
+    #[test]
+    fn test_mask() {
+        use crate::homopolymer_compress_mask;
+
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        let (compressed, mask) = homopolymer_compress_mask(input.iter().cloned());
+        assert_eq!(compressed, b"ACARTGTGTJASAI");
+        assert_eq!(mask.len(), input.len());
+        assert_eq!(mask.count_ones(), compressed.len());
+        let masked: Vec<_> = input
+            .iter()
+            .zip(mask.iter())
+            .filter(|(_, bit)| **bit)
+            .map(|(item, _)| *item)
+            .collect();
+        assert_eq!(masked, compressed);
+
+        let (compressed, mask) = homopolymer_compress_mask(iter::empty::<u8>());
+        assert!(compressed.is_empty());
+        assert!(mask.is_empty());
+    }
+
+    #[test]
+    fn test_stateful_compression() {
+        let input = b"ACAARRRTGGGTGTJASAAAI";
+        let expected = Vec::from_iter(homopolymer_compress(input.iter().cloned()));
+        for split in 0..input.len() {
+            let mut compressor = HomopolymerCompressor::new();
+            let mut actual: Vec<_> = compressor
+                .compress(input[..split].iter().cloned())
+                .collect();
+            actual.extend(compressor.compress(input[split..].iter().cloned()));
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_stateful_compression_with_previous() {
+        let mut compressor = HomopolymerCompressor::with_previous(b'A');
+        assert_eq!(
+            compressor
+                .compress(b"AACGG".iter().cloned())
+                .collect::<Vec<_>>(),
+            b"CG"
+        );
+        let mut compressor = HomopolymerCompressor::with_previous(b'T');
+        assert_eq!(
+            compressor
+                .compress(b"AACGG".iter().cloned())
+                .collect::<Vec<_>>(),
+            b"ACG"
+        );
+    }
 }
@@ -1,55 +1,772 @@
 use bio::io::fasta;
 use cbor::Encoder;
-use clap::Parser;
+use clap::{ArgEnum, Parser};
 use crossbeam::{channel, thread};
-use homopolymer_compress::{homopolymer_compress, homopolymer_compress_with_hodeco_map};
-use log::{info, LevelFilter};
+use homopolymer_compress::analysis::{
+    compressed_gc_content, long_runs, shannon_entropy, MostCompressible, RunLengthQuantiles,
+};
+use homopolymer_compress::cigar::to_cigar;
+use homopolymer_compress::concat::ConcatWriter;
+use homopolymer_compress::config::CompressionConfig;
+use homopolymer_compress::fai::{FaiIndex, Region};
+use homopolymer_compress::fasta::{
+    compress_fasta_streaming, find_resume_point, FastaWriter, ResumePoint,
+};
+use homopolymer_compress::hodeco::packed::PackedHodecoMapWriter;
+use homopolymer_compress::hodeco::{Checksum, ChecksumAlgorithm, HodecoMap};
+use homopolymer_compress::minimizer::bottom_k_sketch;
+use homopolymer_compress::pipeline::{run_pipeline, PipelineConfig, RunReport, Stats};
+use homopolymer_compress::plain::{plain_records, PlainWriter};
+#[cfg(feature = "twobit")]
+use homopolymer_compress::twobit::{TwoBitReader, TwoBitWriter};
+use homopolymer_compress::{
+    homopolymer_compress_clamp, homopolymer_compress_with_run_lengths, longest_run,
+};
+use log::{debug, info, warn, LevelFilter, Log, Metadata, Record};
 use simplelog::{ColorChoice, TermLogger, TerminalMode};
-use std::fs::File;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
 use std::iter;
-use std::path::PathBuf;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
 struct Configuration {
     /// The input file.
-    #[clap(index = 1, parse(from_os_str))]
-    input: PathBuf,
+    #[clap(index = 1, parse(from_os_str), required_unless_present_any = &["inputs", "sequence"])]
+    input: Option<PathBuf>,
+
+    /// The format of the input file.
+    /// With `plain`, each line of the input is a sequence, and the line number (starting from one) is used as its id.
+    /// Lines may end in `\n`, `\r\n` or `\r`.
+    /// With `2bit`, the input is a UCSC `.2bit` file, where `N` blocks are decoded as `N`s and soft-masked blocks as lower case bases.
+    /// Reading `.2bit` files requires the `twobit` feature.
+    #[clap(long, arg_enum, default_value = "fasta")]
+    input_format: InputFormat,
+
+    /// Only compress the given region of the input, written as `name:start-end` with 1-based inclusive coordinates, or as `name` for a whole sequence.
+    /// The region is read directly from its position in the input using a samtools fasta index, without reading the rest of the input.
+    /// The region is output as a single record whose id is the region.
+    #[clap(long)]
+    region: Option<Region>,
+
+    /// The samtools fasta index (`.fai`) of the input used with `--region`.
+    /// Defaults to the input file with `.fai` appended, as created by `samtools faidx`.
+    #[clap(long, parse(from_os_str), requires = "region")]
+    fai: Option<PathBuf>,
 
     /// The output file. If not given, outputting to stdout.
     #[clap(index = 2, parse(from_os_str))]
     output: Option<PathBuf>,
 
+    /// Additional files to write the compressed sequences to, where the format of each file is inferred from its extension.
+    /// Files ending in `.fa`, `.fasta` or `.fna` are written as fasta, and files ending in `.2bit` as UCSC `.2bit`, which requires the `twobit` feature.
+    /// A `.2bit` file is held in memory with two bits per base until all records are compressed, and stores all bases other than `ACGT` as `N`.
+    /// Each record is compressed once and written to the output and all additional files.
+    /// The hodeco map applies to all of them, since they contain the same compressed sequences, up to bases that a format cannot represent.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    also_output: Vec<PathBuf>,
+
+    /// Resume an interrupted run by appending to the existing output file, skipping all input records whose ids already appear in it.
+    /// The last record of the existing output is removed and written again, since it may have been cut off when the run was interrupted.
+    /// Records are matched by id, so the ids of the input records must be unique, but the output order does not need to match the input order, e.g. when using multiple compute threads.
+    /// Outputs other than the compressed sequences are not supported in this mode.
+    #[clap(
+        long,
+        requires = "output",
+        conflicts_with_all = &["hodeco-map-output", "hodeco-map", "runs-tsv", "bedgraph", "long-runs-bed", "cigar", "dump-map", "run-length-histogram", "report-longest-run", "gc-content", "sketch", "debug-pairs", "concat", "also-output"]
+    )]
+    resume: bool,
+
+    /// Write the output file and the hodeco map to temporary files next to them, and rename them to their targets only once all records were written.
+    /// The temporary files are named like their targets with an additional `.tmp` suffix, and are removed if the program fails.
+    /// This way, the output files never exist in a half-written state, e.g. for pipelines that check for their existence.
+    #[clap(long, conflicts_with_all = &["resume", "inputs", "stream"])]
+    atomic: bool,
+
+    /// The file to write a machine-readable summary of the run to at the end, as a single JSON object.
+    /// The object always has the fields `records_processed`, `records_skipped`, `input_bytes`, `output_sequence_bytes`, `ratio` and `elapsed_seconds`.
+    /// Skipped records are those dropped by `--skip-empty`, `--skip-errors` or a filter like `--min-entropy` or `--length-range`, and neither they nor their bytes are counted as processed.
+    /// The byte counts only include sequences, but no ids, descriptions or line breaks.
+    /// The ratio is the number of output sequence bytes divided by the number of input bytes, and is one if there are no input bytes.
+    #[clap(long, parse(from_os_str), conflicts_with_all = &["inputs", "stream"])]
+    report: Option<PathBuf>,
+
     /// The file to output the map used to homopolymer decompress the output.
     #[clap(index = 3, parse(from_os_str))]
     hodeco_map_output: Option<PathBuf>,
 
+    /// The file to output the map used to homopolymer decompress the output, as alternative to the positional argument.
+    /// If this is given without an output file, then only the map is written and the compressed sequences are discarded.
+    #[clap(long, parse(from_os_str), conflicts_with = "hodeco-map-output")]
+    hodeco_map: Option<PathBuf>,
+
+    /// Store a checksum of each original sequence in the hodeco map, computed with the given algorithm (fnv1a64 or crc32).
+    /// The checksum allows to detect if a compressed sequence is decompressed with the wrong map.
+    /// If given without a value, fnv1a64 is used.
+    #[clap(
+        long,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "fnv1a64"
+    )]
+    hodeco_checksum: Option<ChecksumAlgorithm>,
+
+    /// The format of the hodeco map.
+    /// With `cbor`, each record is a cbor array of its id and its run starts followed by the length of the original sequence.
+    /// With `packed`, the map is written in a compact binary format that stores the run lengths as varints,
+    /// see the `hodeco::packed` module of the library for a description and a reader.
+    #[clap(long, arg_enum, default_value = "cbor")]
+    hodeco_format: HodecoFormat,
+
+    /// The file to output a table of all homopolymer runs to, in tab-separated format.
+    /// Each row describes one run with the columns `record_id`, `run_index`, `base`, `start` and `length`,
+    /// where `start` is the index of the first base of the run in the original sequence.
+    #[clap(long, parse(from_os_str))]
+    runs_tsv: Option<PathBuf>,
+
+    /// The file to output the runs of each record to in a CIGAR-like encoding, e.g. `3A1C2T` for `AAACTT`.
+    /// Each line contains the record id and the encoding separated by a tab.
+    /// See the `cigar` module of the library for the exact grammar and a parser.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    cigar: Option<PathBuf>,
+
+    /// The file to output the lengths of the homopolymer runs of each record to in a human-readable format.
+    /// Each line has the form `id: len1 len2 len3 ...`.
+    /// The lines are sorted, such that the file is byte-identical between runs on the same input, regardless of the number of compute threads.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    dump_map: Option<PathBuf>,
+
+    /// The file to output a BedGraph track of the lengths of the homopolymer runs to, e.g. for viewing in a genome browser.
+    /// Each record is treated as a chromosome, and each run is one interval whose value is the length of the run.
+    /// Coordinates are 0-based and intervals are half-open, as usual for BedGraph.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    bedgraph: Option<PathBuf>,
+
+    /// The file to output a BED track of all homopolymer runs that are at least `--long-runs-min-length` long to, e.g. for flagging problematic regions.
+    /// Each row has the columns `chrom`, `start`, `end`, `name` and `score`,
+    /// where `chrom` is the record id, `name` is the base of the run and `score` is the length of the run.
+    /// Coordinates are 0-based and intervals are half-open, as usual for BED.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    long_runs_bed: Option<PathBuf>,
+
+    /// The minimum length of a homopolymer run to be output to `--long-runs-bed`.
+    #[clap(long, default_value = "10")]
+    long_runs_min_length: usize,
+
+    /// The file to output a histogram of the lengths of the homopolymer runs of each symbol to, in tab-separated format.
+    /// Each row has the columns `symbol`, `run_length` and `count`, and rows are sorted by symbol and run length.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    run_length_histogram: Option<PathBuf>,
+
+    /// Log the 50th, 90th and 99th percentile of the lengths of all homopolymer runs at the end.
+    /// The percentiles are exact, since they are computed from the number of runs of each distinct length.
+    #[clap(long, conflicts_with = "stream-output")]
+    run_quantiles: bool,
+
+    /// Report the ids and compression factors of the given number of records with the highest compression factor to stderr at the end,
+    /// where the compression factor is the length of the original sequence divided by the length of the compressed sequence,
+    /// i.e. the inverse of the compression ratio reported elsewhere.
+    /// Records with equal factors are ranked by id, and records with an empty sequence are not ranked.
+    #[clap(long, conflicts_with = "stream-output")]
+    top_compressible: Option<usize>,
+
+    /// The file to output the longest homopolymer run of each record to, in tab-separated format.
+    /// Each row has the columns `record_id`, `symbol`, `length` and `start`, where `start` is the index of the first base of the run in the original sequence.
+    /// If a record has multiple longest runs, the first one is reported, and records with an empty sequence have no row.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    report_longest_run: Option<PathBuf>,
+
+    /// The file to output the GC content of each record in homopolymer compressed space to, in tab-separated format.
+    /// Each row has the columns `record_id` and `gc_content`, where `gc_content` is the fraction of homopolymer runs of the original sequence whose base is `G` or `C`.
+    /// Records with an empty sequence have a GC content of zero.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    gc_content: Option<PathBuf>,
+
+    /// Compute a bottom-SIZE MinHash sketch of each compressed sequence and write it to `--sketch-output`, e.g. for clustering records by their similarity in compressed space.
+    /// The sketch consists of the SIZE smallest distinct hashes of the k-mers of the compressed sequence, where k is `--sketch-k`.
+    /// The k-mers are hashed with a polynomial rolling hash modulo 2^64 with the 64-bit FNV prime as base, mixed with the finaliser of the 64-bit MurmurHash3.
+    #[clap(
+        long,
+        value_name = "SIZE",
+        requires = "sketch-output",
+        conflicts_with = "stream-output"
+    )]
+    sketch: Option<usize>,
+
+    /// The file to output the sketches of `--sketch` to.
+    /// Each line contains the record id and the hashes of its sketch in increasing order, separated by a tab, e.g. `id\thash1,hash2,hash3`.
+    #[clap(long, parse(from_os_str), requires = "sketch")]
+    sketch_output: Option<PathBuf>,
+
+    /// The length of the k-mers hashed by `--sketch`, counted in the compressed sequence.
+    #[clap(long, default_value = "21", parse(try_from_str = parse_positive))]
+    sketch_k: usize,
+
+    /// The fasta file to output each original sequence next to its compressed sequence to, e.g. for diffing them while debugging.
+    /// Each record is written twice, first with the id suffix `.orig` and its original sequence, and then with the id suffix `.hoco` and its compressed sequence.
+    /// This doubles the output size, so it is only written if requested.
+    #[clap(long, parse(from_os_str), conflicts_with = "stream-output")]
+    debug_pairs: Option<PathBuf>,
+
+    /// Write all sequences exactly as they were read, without compressing them.
+    /// This still reads, transfers and writes all records like a normal run, so comparing the runtime with a normal run shows how much time is spent on compression instead of input and output.
+    #[clap(
+        long,
+        conflicts_with_all = &["only", "normalize-length", "min-run", "max-run", "hodeco-map-output", "hodeco-map", "runs-tsv", "bedgraph", "long-runs-bed", "cigar", "dump-map", "run-length-histogram", "run-quantiles", "stream-output"]
+    )]
+    no_compress: bool,
+
+    /// Replace each compressed homopolymer run with exactly this many copies of its symbol, instead of a single one.
+    /// For example, with a value of 3, both `AA` and `AAAAA` become `AAA`.
+    /// Can be combined with `--only` and `--min-run`, which select the runs that are compressed.
+    /// Lengthened runs cannot be mapped back to the input, so the hodeco map is not supported in this mode.
+    #[clap(
+        long,
+        conflicts_with_all = &["max-run", "hodeco-map-output", "hodeco-map"]
+    )]
+    normalize_length: Option<usize>,
+
+    /// Only compress homopolymer runs of at least this length, and keep shorter runs unchanged.
+    /// Can be combined with `--only`, `--max-run` and `--normalize-length`, and defaults to compressing all runs.
+    #[clap(long)]
+    min_run: Option<usize>,
+
+    /// Shorten compressed homopolymer runs to this many copies of their symbol instead of a single one, and keep runs that are shorter than this unchanged.
+    /// The runs shorter than `--min-run` and the runs excluded by `--only` are never changed.
+    #[clap(long, parse(try_from_str = parse_positive))]
+    max_run: Option<usize>,
+
+    /// Additionally compress each sequence with a baseline that only collapses runs of at least MIN_RUN items, as `--min-run` does,
+    /// and log the total lengths of the output and of the baseline at the end.
+    /// This quantifies how much of the size reduction is due to short runs.
+    /// A MIN_RUN of two collapses the same runs as homopolymer compression, so the baseline is only interesting with a MIN_RUN of at least three.
+    #[clap(
+        long,
+        value_name = "MIN_RUN",
+        conflicts_with_all = &["no-compress", "stream-output"]
+    )]
+    compare_baseline: Option<usize>,
+
+    /// Drop records whose compressed sequence has a Shannon entropy below this value, in bits per symbol.
+    /// Empty sequences have an entropy of zero.
+    #[clap(long, conflicts_with = "stream-output")]
+    min_entropy: Option<f64>,
+
+    /// Only compress records whose original sequence length is within the given range, written as `MIN:MAX`, and drop all others.
+    /// Both bounds are inclusive, e.g. `1000:5000` keeps records with 1000 to 5000 bases.
+    #[clap(
+        long,
+        value_name = "MIN:MAX",
+        parse(try_from_str = parse_length_range),
+        conflicts_with_all = &["stream", "stream-output", "inputs", "sequence"]
+    )]
+    length_range: Option<(usize, usize)>,
+
+    /// Convert the sequences to upper or lower case before compressing.
+    /// This allows to compress runs that are only partially soft-masked, but loses the masking.
+    #[clap(long, arg_enum, default_value = "keep")]
+    case: Case,
+
+    /// Print the first N homopolymer runs of the first record to stderr as `base×length`, e.g. `A×3 C×1 T×2`, before compressing.
+    /// This does not affect any of the outputs.
+    #[clap(long, value_name = "N")]
+    preview: Option<usize>,
+
+    /// Drop records with an empty sequence instead of outputting them as empty records.
+    #[clap(long)]
+    skip_empty: bool,
+
+    /// The format of the log messages written to stderr.
+    /// With `json`, each message is written as a JSON object with the fields `timestamp`, `level`, `thread` and `message`.
+    #[clap(long, arg_enum, default_value = "human")]
+    log_format: LogFormat,
+
+    /// Log the name of the thread that processed each record, together with the length of the record and the time it took, at debug level.
+    /// This helps to find records that dominate the runtime, e.g. when the compute threads are unevenly loaded.
+    #[clap(long, conflicts_with = "stream-output")]
+    trace_records: bool,
+
+    /// Fail if a sequence contains a byte that is not printable ASCII, i.e. a control byte, whitespace or a byte of a non-ASCII UTF-8 character.
+    /// The error names the record and the 0-based offset of the first such byte in its sequence.
+    /// This is a sanity check against corrupted input, which would otherwise be compressed like any other symbol.
+    #[clap(long, conflicts_with = "stream-output")]
+    ascii_only: bool,
+
+    /// Log and skip records that fail validation, e.g. with `--ascii-only`, instead of aborting the whole run.
+    /// The number of skipped records is logged at the end, and the program exits with code 2 if any record was skipped.
+    /// With `--input-format plain`, lines that are not valid UTF-8 are skipped and counted as well.
+    /// Other errors while reading the input still abort the run, since e.g. the fasta parser cannot continue after a malformed record.
+    #[clap(long, requires = "ascii-only")]
+    skip_errors: bool,
+
+    /// Only compress runs of the given symbols, e.g. `A` or `AT`, and keep all other runs unchanged.
+    #[clap(long)]
+    only: Option<String>,
+
     /// The number of compute threads to use for compressing.
     /// The program uses two extra threads for reading and writing the input and output files, which are not part of this number.
     /// It is likely that a very low number of threads is enough, since homopolymer compression is a very fast algorithm.
     #[clap(long, default_value = "1")]
     threads: usize,
 
+    /// Choose the number of compute threads automatically instead of using `--threads`.
+    /// The records of a prefix of the input are compressed with 1, 2, 4 and 8 threads,
+    /// and the fastest number of threads is used for compressing the whole input.
+    #[clap(long, conflicts_with_all = &["threads", "stream-output"])]
+    autotune: bool,
+
+    /// Choose the number of compute threads and the buffer sizes automatically from a prefix of the input.
+    /// The number of threads is chosen as with `--autotune`, and the buffers are sized from the mean size of the records in the prefix,
+    /// such that the records buffered between the threads fit into `--max-memory`, or into 1 GiB if it is not given.
+    /// The chosen values are logged, such that they can be passed explicitly in future runs.
+    #[clap(
+        long,
+        conflicts_with_all = &["threads", "autotune", "buffer-size", "input-buffer-size", "output-buffer-size", "stream-output"]
+    )]
+    auto_config: bool,
+
     /// The size of the buffers between input and compute threads, and compute threads and output threads.
+    /// Use `--input-buffer-size` and `--output-buffer-size` to set the two buffers independently.
     #[clap(long, default_value = "32768")]
     buffer_size: usize,
+
+    /// Limit the memory used by records that were read but not yet written to roughly this many bytes.
+    /// If the limit is reached, reading waits until enough records were written, even if the buffers are not full.
+    /// Only the ids, descriptions and sequences of the records are counted, so the actual memory usage is somewhat higher.
+    #[clap(long, value_name = "BYTES")]
+    max_memory: Option<usize>,
+
+    /// Write the compressed sequences and the hodeco map in the order of the input records, regardless of the number of compute threads.
+    /// This makes the output byte-identical between runs on the same input, e.g. for comparing checksums.
+    /// Results that are computed early are held back until all records before them were written, which costs some memory.
+    #[clap(long, conflicts_with_all = &["inputs", "stream"])]
+    ordered: bool,
+
+    /// The number of records buffered between the input thread and the compute threads.
+    /// Defaults to `--buffer-size`.
+    #[clap(long)]
+    input_buffer_size: Option<usize>,
+
+    /// The number of records buffered between the compute threads and the output thread.
+    /// Defaults to `--buffer-size`.
+    /// Increasing this may help if writing the output is slower than reading the input.
+    #[clap(long)]
+    output_buffer_size: Option<usize>,
+
+    /// Compress each record while writing it, instead of collecting the compressed sequence in memory first.
+    /// In this mode, compression happens in the output thread, and no compute threads are used.
+    /// Cannot be combined with outputting a hodeco map or a runs table.
+    #[clap(
+        long,
+        conflicts_with_all = &["hodeco-map-output", "hodeco-map", "runs-tsv"]
+    )]
+    stream_output: bool,
+
+    /// Write the compressed sequences as a single concatenated stream instead of fasta, terminating each sequence with `--concat-separator`.
+    /// The offset and length of each sequence in the stream are written to `--concat-index`.
+    #[clap(long, requires = "concat-index", conflicts_with = "stream-output")]
+    concat: bool,
+
+    /// The file to write the index of the concatenated stream to when using `--concat`, in tab-separated format.
+    /// Each row has the columns `record_id`, `offset` and `length`, where `offset` is the position of the first byte of the sequence in the stream,
+    /// and `length` does not include the separator.
+    #[clap(long, parse(from_os_str), requires = "concat")]
+    concat_index: Option<PathBuf>,
+
+    /// The byte written after each sequence when using `--concat`.
+    #[clap(long, default_value = "$", parse(try_from_str = parse_byte))]
+    concat_separator: u8,
+
+    /// The number of bytes per line of the output sequences.
+    /// If zero, each sequence is written on a single line, which is required by some tools that cannot parse wrapped fasta.
+    #[clap(long, default_value = "0")]
+    output_line_width: usize,
+
+    /// Append the compression ratio of each record to its description as `hoco_ratio=0.42`, i.e. the length of the compressed sequence divided by the length of the original sequence.
+    /// An existing description is kept, and the ratio is separated from it by a space.
+    /// Records with an empty sequence have a ratio of one.
+    #[clap(long, conflicts_with = "stream-output")]
+    annotate_ratio: bool,
+
+    /// The format of the output.
+    /// With `plain`, each compressed sequence is written on its own line without its id and description, as read by `--input-format plain`.
+    #[clap(long, arg_enum, default_value = "fasta", conflicts_with_all = &["resume", "concat"])]
+    output_format: OutputFormat,
+
+    /// Compress the input while streaming it byte by byte, without ever holding a whole record or line in memory.
+    /// This allows to compress records of arbitrary length, e.g. a whole genome as a single fasta record, with constant memory.
+    /// Streaming happens in a single thread, and supports only fasta input and output, and no outputs other than the compressed sequences.
+    #[clap(
+        long,
+        conflicts_with_all = &["inputs", "region", "resume", "also-output", "hodeco-map-output", "hodeco-map", "runs-tsv", "bedgraph", "long-runs-bed", "cigar", "dump-map", "run-length-histogram", "run-quantiles", "top-compressible", "report-longest-run", "gc-content", "sketch", "debug-pairs", "no-compress", "normalize-length", "min-run", "max-run", "compare-baseline", "min-entropy", "preview", "skip-empty", "trace-records", "annotate-ratio", "ascii-only", "only", "autotune", "auto-config", "stream-output", "concat", "output-format"]
+    )]
+    stream: bool,
+
+    /// Compress the given sequence instead of an input file, and print the compressed sequence to stdout, e.g. for quick tests.
+    /// Only `--case` and the options that select how runs are compressed, like `--only` and `--min-run`, apply to the sequence.
+    /// With `--hodeco-map`, the map is printed to stderr as the compressed symbols with the offsets of their runs in the given sequence, e.g. `A@0 C@3`, instead of being written to a file.
+    #[clap(
+        long,
+        conflicts_with_all = &["input", "inputs", "region", "resume", "stream", "stream-output", "also-output", "hodeco-map-output", "runs-tsv", "bedgraph", "long-runs-bed", "cigar", "dump-map", "run-length-histogram", "run-quantiles", "top-compressible", "report-longest-run", "gc-content", "sketch", "debug-pairs", "no-compress", "compare-baseline", "min-entropy", "preview", "skip-empty", "trace-records", "annotate-ratio", "ascii-only", "skip-errors", "autotune", "auto-config", "concat", "output-format", "atomic", "report"]
+    )]
+    sequence: Option<String>,
+
+    /// Multiple input files to compress independently of each other.
+    /// Each file is compressed into a file of the same name in `--output-directory`.
+    /// Replaces the positional input and output arguments, and supports no outputs other than the compressed sequences.
+    #[clap(
+        long,
+        parse(from_os_str),
+        multiple_values = true,
+        conflicts_with_all = &["input", "output", "region", "hodeco-map-output", "hodeco-map", "runs-tsv", "bedgraph", "long-runs-bed", "cigar", "dump-map", "run-length-histogram", "run-quantiles", "top-compressible", "report-longest-run", "gc-content", "sketch", "debug-pairs", "trace-records", "annotate-ratio", "ascii-only", "skip-errors", "compare-baseline", "no-compress", "also-output", "stream-output", "preview", "concat", "autotune", "auto-config"],
+        requires = "output-directory"
+    )]
+    inputs: Vec<PathBuf>,
+
+    /// The directory to write the compressed files to when using `--inputs`.
+    #[clap(long, parse(from_os_str))]
+    output_directory: Option<PathBuf>,
+
+    /// The number of files to compress concurrently when using `--inputs`.
+    /// Each file is read, compressed and written by a single thread, which is faster than splitting the records of a file over multiple compute threads if there are many small files.
+    #[clap(long, default_value = "1", parse(try_from_str = parse_positive))]
+    file_parallel: usize,
 }
 
-fn initialise_logging() {
-    TermLogger::init(
-        LevelFilter::Debug,
-        Default::default(),
-        TerminalMode::Stderr,
-        ColorChoice::Auto,
-    )
-    .unwrap();
-    info!("Logging initialised successfully")
+impl Configuration {
+    /// The pipeline options given on the command line.
+    fn pipeline_config(&self) -> PipelineConfig {
+        PipelineConfig {
+            compression: self.compression_config(),
+            threads: self.threads,
+            input_buffer_size: self.input_buffer_size(),
+            output_buffer_size: self.output_buffer_size(),
+            skip_empty: self.skip_empty,
+            max_memory: self.max_memory,
+            ordered: self.ordered,
+        }
+    }
+
+    /// The compression options given on the command line.
+    fn compression_config(&self) -> CompressionConfig<u8> {
+        CompressionConfig {
+            only: self.only.as_ref().map(|only| only.bytes().collect()),
+            min_run: self.min_run.unwrap_or(1),
+            max_run: self.max_run.unwrap_or(1),
+            normalize_length: self.normalize_length,
+        }
+    }
+
+    /// The capacity of the channel between the input thread and the compute threads.
+    fn input_buffer_size(&self) -> usize {
+        self.input_buffer_size.unwrap_or(self.buffer_size)
+    }
+
+    /// The capacity of the channel between the compute threads and the output thread.
+    fn output_buffer_size(&self) -> usize {
+        self.output_buffer_size.unwrap_or(self.buffer_size)
+    }
 }
 
-fn main() {
-    let configuration = Configuration::parse();
-    initialise_logging();
+/// Parse a positive integer.
+fn parse_positive(string: &str) -> Result<usize, String> {
+    match string.parse() {
+        Ok(0) => Err("must be positive".to_owned()),
+        Ok(value) => Ok(value),
+        Err(error) => Err(format!("{error}")),
+    }
+}
+
+/// Parse an inclusive range of lengths written as `MIN:MAX`.
+fn parse_length_range(string: &str) -> Result<(usize, usize), String> {
+    let (min, max) = string
+        .split_once(':')
+        .ok_or_else(|| format!("expected MIN:MAX, but got {string:?}"))?;
+    let min: usize = min.parse().map_err(|error| format!("{error}"))?;
+    let max: usize = max.parse().map_err(|error| format!("{error}"))?;
+    if min > max {
+        return Err(format!(
+            "MIN must not be greater than MAX, but got {string:?}"
+        ));
+    }
+    Ok((min, max))
+}
 
-    let input = configuration.input;
+/// Parse a string consisting of exactly one byte.
+fn parse_byte(string: &str) -> Result<u8, String> {
+    if let [byte] = string.as_bytes() {
+        Ok(*byte)
+    } else {
+        Err(format!("expected a single byte, but got {string:?}"))
+    }
+}
+
+/// The writer of the compressed sequences, see `--output-format`.
+enum SequenceWriter<W: Write> {
+    /// Fasta records.
+    Fasta(FastaWriter<W>),
+    /// One sequence per line.
+    Plain(PlainWriter<W>),
+}
+
+impl<W: Write> SequenceWriter<W> {
+    /// Create a writer of the given format, where `line_width` only applies to fasta.
+    fn new(writer: W, output_format: OutputFormat, line_width: usize) -> Self {
+        match output_format {
+            OutputFormat::Fasta => Self::Fasta(FastaWriter::new(writer, line_width)),
+            OutputFormat::Plain => Self::Plain(PlainWriter::new(writer)),
+        }
+    }
+
+    fn write_record(
+        &mut self,
+        id: &str,
+        description: Option<&str>,
+        sequence: impl IntoIterator<Item = u8>,
+    ) -> std::io::Result<()> {
+        match self {
+            Self::Fasta(writer) => writer.write_record(id, description, sequence),
+            Self::Plain(writer) => writer.write_sequence(sequence),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Fasta(writer) => writer.flush(),
+            Self::Plain(writer) => writer.flush(),
+        }
+    }
+}
+
+/// An additional output file of the compressed sequences, see `--also-output`.
+enum AdditionalOutput {
+    /// A fasta file.
+    Fasta(FastaWriter<BufWriter<File>>),
+    /// A `.2bit` file.
+    #[cfg(feature = "twobit")]
+    TwoBit(TwoBitWriter<BufWriter<File>>),
+}
+
+impl AdditionalOutput {
+    /// Create the given file, with the format given by its extension.
+    fn create(path: &Path, line_width: usize) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("");
+        let create =
+            || {
+                BufWriter::new(File::create(path).unwrap_or_else(|error| {
+                    panic!("Cannot create additional output file: {error:?}")
+                }))
+            };
+        match extension {
+            "fa" | "fasta" | "fna" => Self::Fasta(FastaWriter::new(create(), line_width)),
+            #[cfg(feature = "twobit")]
+            "2bit" => Self::TwoBit(TwoBitWriter::new(create())),
+            _ => panic!(
+                "Cannot infer the format of additional output file {path:?} from its extension"
+            ),
+        }
+    }
+
+    fn write_record(
+        &mut self,
+        id: &str,
+        description: Option<&str>,
+        sequence: &[u8],
+    ) -> std::io::Result<()> {
+        match self {
+            Self::Fasta(writer) => writer.write_record(id, description, sequence.iter().copied()),
+            #[cfg(feature = "twobit")]
+            Self::TwoBit(writer) => writer.write_record(id, sequence),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Fasta(mut writer) => writer.flush(),
+            #[cfg(feature = "twobit")]
+            Self::TwoBit(writer) => writer.finish().map(|_| ()),
+        }
+    }
+}
+
+/// A compressed record sent from a compute thread to the output thread.
+struct CompressedRecord {
+    id: String,
+    description: Option<String>,
+    sequence: Vec<u8>,
+    original_len: usize,
+    hodeco_map: Option<HodecoMap>,
+    longest_run: Option<(u8, usize, usize)>,
+    gc_content: Option<f64>,
+    sketch: Option<Vec<u64>>,
+    original: Option<Vec<u8>>,
+    /// The homopolymer runs of the original sequence, regardless of which runs are compressed.
+    runs: Option<Vec<(u8, usize)>>,
+    baseline_len: Option<usize>,
+}
+
+/// The format of the input.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum InputFormat {
+    /// Fasta records.
+    Fasta,
+    /// One sequence per line.
+    Plain,
+    /// A UCSC `.2bit` file.
+    #[cfg(feature = "twobit")]
+    #[clap(name = "2bit")]
+    TwoBit,
+}
+
+/// The format of the output.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Fasta records.
+    Fasta,
+    /// One sequence per line.
+    Plain,
+}
+
+/// A case conversion applied to the sequences before compressing.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Case {
+    /// Convert to upper case.
+    Upper,
+    /// Convert to lower case.
+    Lower,
+    /// Keep the case of the input.
+    Keep,
+}
+
+impl Case {
+    /// Apply this case conversion to the given byte.
+    fn apply_to_byte(&self, byte: u8) -> u8 {
+        match self {
+            Self::Upper => byte.to_ascii_uppercase(),
+            Self::Lower => byte.to_ascii_lowercase(),
+            Self::Keep => byte,
+        }
+    }
+
+    /// Apply this case conversion to the given sequence.
+    fn apply<'sequence>(&self, sequence: &'sequence [u8]) -> Cow<'sequence, [u8]> {
+        match self {
+            Self::Upper => Cow::Owned(sequence.to_ascii_uppercase()),
+            Self::Lower => Cow::Owned(sequence.to_ascii_lowercase()),
+            Self::Keep => Cow::Borrowed(sequence),
+        }
+    }
+}
+
+/// The format of the hodeco map output.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum HodecoFormat {
+    /// A sequence of cbor arrays.
+    Cbor,
+    /// A compact binary format with varint-encoded run lengths.
+    Packed,
+}
+
+/// The format of the log output.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    /// Human-readable, possibly coloured lines.
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+/// A logger that writes each log record to stderr as a JSON object on its own line.
+///
+/// Each object has the fields `timestamp` (seconds since the Unix epoch), `level`, `thread` and `message`.
+struct JsonLogger {
+    level: LevelFilter,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs_f64())
+            .unwrap_or_default();
+        let thread = std::thread::current();
+        let line = format!(
+            "{{\"timestamp\":{timestamp:.3},\"level\":\"{}\",\"thread\":{},\"message\":{}}}\n",
+            record.level(),
+            json_string(thread.name().unwrap_or("unnamed")),
+            json_string(&record.args().to_string()),
+        );
+        // Errors cannot be reported anywhere else, so they are ignored.
+        let _ = std::io::stderr().lock().write_all(line.as_bytes());
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// Encode the given string as a quoted JSON string.
+fn json_string(string: &str) -> String {
+    let mut result = String::with_capacity(string.len() + 2);
+    result.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            character if character.is_control() => {
+                result.push_str(&format!("\\u{:04x}", u32::from(character)))
+            }
+            character => result.push(character),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn initialise_logging(log_format: LogFormat) {
+    match log_format {
+        LogFormat::Human => TermLogger::init(
+            LevelFilter::Debug,
+            Default::default(),
+            TerminalMode::Stderr,
+            ColorChoice::Auto,
+        )
+        .unwrap(),
+        LogFormat::Json => {
+            log::set_boxed_logger(Box::new(JsonLogger {
+                level: LevelFilter::Debug,
+            }))
+            .unwrap();
+            log::set_max_level(LevelFilter::Debug);
+        }
+    }
+    info!("Logging initialised successfully")
+}
+
+fn check_input_extension(input: &Path, input_format: InputFormat) {
+    if input_format != InputFormat::Fasta {
+        return;
+    }
     if let Some(extension) = input.extension() {
         if extension != "fasta" && extension != "fa" {
             panic!("Only fasta files supported at the moment, must end in .fa or .fasta, but ends in: {extension:?}");
@@ -57,123 +774,1161 @@ fn main() {
     } else {
         panic!("Only fasta files supported at the moment, must end in .fa or .fasta, but no extension found: {input:?}");
     }
+}
 
-    thread::scope(|scope| {
-        let input_file =
-            File::open(&input).unwrap_or_else(|error| panic!("Cannot open input file: {error:?}"));
-        let (input_sender, input_receiver) = channel::bounded(configuration.buffer_size);
-        scope
-            .builder()
-            .name("input_thread".to_string())
-            .spawn(move |_| {
-                for record in fasta::Reader::new(input_file).records() {
-                    let record = record
-                        .unwrap_or_else(|error| panic!("Cannot read fasta record: {error:?}"));
-                    input_sender
-                        .send(record)
-                        .unwrap_or_else(|error| panic!("Cannot send fasta record: {error:?}"));
-                }
+/// Read the records of the given input file in the given format.
+/// Lines of plain input become records without description, whose id is their line number.
+fn read_records(
+    input_file: File,
+    input_format: InputFormat,
+) -> Box<dyn Iterator<Item = std::io::Result<fasta::Record>> + Send> {
+    match input_format {
+        InputFormat::Fasta => Box::new(fasta::Reader::new(input_file).records()),
+        InputFormat::Plain => Box::new(plain_records(BufReader::new(input_file))),
+        #[cfg(feature = "twobit")]
+        InputFormat::TwoBit => Box::new(
+            TwoBitReader::new(BufReader::new(input_file))
+                .unwrap_or_else(|error| panic!("Cannot read 2bit header: {error:?}"))
+                .map(|record| {
+                    record.map(|record| {
+                        fasta::Record::with_attrs(&record.name, None, &record.sequence)
+                    })
+                }),
+        ),
+    }
+}
+
+/// Create the output file, or open it for appending if resuming.
+fn create_output_file(output: &Path, resume: bool) -> File {
+    if resume {
+        OpenOptions::new().create(true).append(true).open(output)
+    } else {
+        File::create(output)
+    }
+    .unwrap_or_else(|error| panic!("Cannot create output file: {error:?}"))
+}
+
+/// Temporary files that are renamed to their targets once all output was written.
+/// If dropped before, e.g. when a panic unwinds, the temporary files are removed instead.
+struct AtomicOutputs {
+    /// Pairs of temporary files and their targets.
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+impl AtomicOutputs {
+    /// Redirect the output file and the hodeco map of the given configuration to temporary files.
+    /// The temporary files are in the same directory as their targets, such that renaming them is atomic.
+    fn redirect(configuration: &mut Configuration) -> Self {
+        let mut files = Vec::new();
+        for target in [
+            &mut configuration.output,
+            &mut configuration.hodeco_map_output,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            let mut temporary = target.clone().into_os_string();
+            temporary.push(".tmp");
+            let temporary = PathBuf::from(temporary);
+            files.push((temporary.clone(), mem::replace(target, temporary)));
+        }
+        Self { files }
+    }
+
+    /// Rename all temporary files to their targets.
+    fn commit(mut self) {
+        for (temporary, target) in mem::take(&mut self.files) {
+            if let Err(error) = fs::rename(&temporary, &target) {
+                // Remove this temporary file as well, since it is not in `self.files` anymore.
+                let _ = fs::remove_file(&temporary);
+                panic!("Cannot rename {temporary:?} to {target:?}: {error:?}");
+            }
+        }
+    }
+}
+
+impl Drop for AtomicOutputs {
+    fn drop(&mut self) {
+        for (temporary, _) in &self.files {
+            let _ = fs::remove_file(temporary);
+        }
+    }
+}
+
+/// Prepare resuming to write the given output file, returning the ids of the records that were already written.
+/// The output file is truncated after the last completely written record.
+fn prepare_resume(output: &Path) -> HashSet<String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(output)
+        .unwrap_or_else(|error| panic!("Cannot open output file for resuming: {error:?}"));
+    let ResumePoint { ids, offset } = find_resume_point(BufReader::new(&file))
+        .unwrap_or_else(|error| panic!("Cannot read output file for resuming: {error:?}"));
+    file.set_len(offset)
+        .unwrap_or_else(|error| panic!("Cannot truncate output file for resuming: {error:?}"));
+    info!(
+        "Resuming after {} records that were already written",
+        ids.len()
+    );
+    ids
+}
+
+/// Read the given region of the given fasta file as a single record, using the fasta index at `fai` or next to the input.
+fn read_region(input: &Path, fai: Option<&Path>, region: &Region) -> fasta::Record {
+    let fai = fai.map(Path::to_owned).unwrap_or_else(|| {
+        let mut fai = input.as_os_str().to_owned();
+        fai.push(".fai");
+        PathBuf::from(fai)
+    });
+    let index = File::open(&fai)
+        .and_then(|file| FaiIndex::read(BufReader::new(file)))
+        .unwrap_or_else(|error| {
+            panic!("Cannot read fasta index {fai:?}, it can be created with `samtools faidx`: {error:?}")
+        });
+    let mut input_file =
+        File::open(input).unwrap_or_else(|error| panic!("Cannot open input file: {error:?}"));
+    let sequence = index
+        .read_region(&mut input_file, region)
+        .unwrap_or_else(|error| panic!("Cannot read region: {error}"));
+    fasta::Record::with_attrs(&region.to_string(), None, &sequence)
+}
+
+/// Compress the sequence given on the command line and print it, see `--sequence`.
+fn compress_sequence(configuration: &Configuration, sequence: &str) {
+    let case = configuration.case;
+    let runs: Vec<_> = configuration
+        .compression_config()
+        .compress_with_run_lengths(case.apply(sequence.as_bytes()).iter().copied())
+        .collect();
+    println!(
+        "{}",
+        runs.iter()
+            .map(|&(base, _)| char::from(base))
+            .collect::<String>()
+    );
+    if let Some(path) = &configuration.hodeco_map_output {
+        info!("Printing the hodeco map to stderr instead of writing it to {path:?}");
+        let mut offset = 0;
+        let map: Vec<_> = runs
+            .iter()
+            .map(|&(base, length)| {
+                let entry = format!("{}@{offset}", char::from(base));
+                offset += length;
+                entry
             })
-            .unwrap_or_else(|error| panic!("Cannot spawn input thread: {error:?}"));
+            .collect();
+        eprintln!("Hodeco map: {}", map.join(" "));
+    }
+}
 
-        let (output_sender, output_receiver) =
-            channel::bounded::<(String, Option<String>, (Vec<u8>, Option<Vec<usize>>))>(
-                configuration.buffer_size,
-            );
-        let hodeco_map_output = configuration.hodeco_map_output.clone();
-        if let Some(output) = configuration.output {
-            let output_file = File::create(&output)
-                .unwrap_or_else(|error| panic!("Cannot create output file: {error:?}"));
+/// Compress the input with [`compress_fasta_streaming`], see `--stream`.
+fn compress_streaming(configuration: &Configuration, input: &Path) {
+    if configuration.input_format != InputFormat::Fasta {
+        panic!("Streaming is only supported for fasta input");
+    }
+    let input_file =
+        File::open(input).unwrap_or_else(|error| panic!("Cannot open input file: {error:?}"));
+    let output: Box<dyn Write> = if let Some(output) = &configuration.output {
+        Box::new(
+            File::create(output)
+                .unwrap_or_else(|error| panic!("Cannot create output file: {error:?}")),
+        )
+    } else {
+        Box::new(std::io::stdout())
+    };
+    let mut output = BufWriter::new(output);
+    let case = configuration.case;
+    let records = compress_fasta_streaming(
+        BufReader::new(input_file),
+        &mut output,
+        configuration.output_line_width,
+        |byte| case.apply_to_byte(byte),
+    )
+    .unwrap_or_else(|error| panic!("Cannot compress input: {error:?}"));
+    output
+        .flush()
+        .unwrap_or_else(|error| panic!("Cannot flush output: {error:?}"));
+    info!("Compressed {records} records while streaming");
+}
+
+/// The output file of each input file of `--inputs`, which has the same name as the input file but is in the output directory.
+///
+/// Panics if an output file would overwrite an input file, or if two inputs have the same name, such that their outputs would overwrite each other.
+/// Since the outputs are created while other inputs are still read, this is checked before any file is created.
+fn file_parallel_outputs<'input>(
+    inputs: &'input [PathBuf],
+    output_directory: &Path,
+) -> Vec<(&'input PathBuf, PathBuf)> {
+    let canonical_inputs: HashSet<_> = inputs
+        .iter()
+        .map(|input| {
+            input
+                .canonicalize()
+                .unwrap_or_else(|error| panic!("Cannot open input file {input:?}: {error:?}"))
+        })
+        .collect();
+    let canonical_output_directory = output_directory
+        .canonicalize()
+        .unwrap_or_else(|error| panic!("Cannot open output directory: {error:?}"));
+    let mut file_names = HashSet::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let file_name = input
+                .file_name()
+                .unwrap_or_else(|| panic!("Input has no file name: {input:?}"));
+            if !file_names.insert(file_name) {
+                panic!("Multiple inputs are named {file_name:?}, so their output files would overwrite each other");
+            }
+            let output = output_directory.join(file_name);
+            // The output may not exist yet, but it may also be a link to an input.
+            let canonical_output = output
+                .canonicalize()
+                .unwrap_or_else(|_| canonical_output_directory.join(file_name));
+            if canonical_inputs.contains(&canonical_output) {
+                panic!("The output file {output:?} would overwrite an input file");
+            }
+            (input, output)
+        })
+        .collect()
+}
+
+/// Compress each input file into a file of the same name in the output directory, using `--file-parallel` threads that each process a whole file at a time.
+fn compress_files_in_parallel(configuration: &Configuration) {
+    let inputs = &configuration.inputs;
+    let output_directory = configuration
+        .output_directory
+        .as_ref()
+        .unwrap_or_else(|| unreachable!());
+    let input_format = configuration.input_format;
+    let skip_empty = configuration.skip_empty;
+    let case = configuration.case;
+    let output_line_width = configuration.output_line_width;
+    let output_format = configuration.output_format;
+    let min_entropy = configuration.min_entropy;
+    let compression_config = &configuration.compression_config();
+    for input in inputs {
+        check_input_extension(input, input_format);
+    }
+    let outputs = file_parallel_outputs(inputs, output_directory);
+
+    thread::scope(|scope| {
+        let (file_sender, file_receiver) = channel::unbounded();
+        for (input, output) in outputs {
+            file_sender
+                .send((input, output))
+                .unwrap_or_else(|error| panic!("Cannot queue input file: {error:?}"));
+        }
+        drop(file_sender);
+
+        for thread_id in 0..configuration.file_parallel {
+            let file_receiver = file_receiver.clone();
             scope
                 .builder()
-                .name("output_thread".to_string())
+                .name(format!("file_thread_{thread_id}"))
                 .spawn(move |_| {
-                    let mut writer = fasta::Writer::new(output_file);
-                    let mut hodeco_mapping_writer = hodeco_map_output.as_ref().map(|path| {
-                        Encoder::from_writer(File::create(path).unwrap_or_else(|error| {
-                            panic!("Cannot create hodeco mapping output file: {error:?}")
-                        }))
-                    });
-                    while let Ok((id, description, (sequence, hodeco_mapping))) =
-                        output_receiver.recv()
-                    {
-                        writer
-                            .write(&id, description.as_deref(), &sequence)
-                            .unwrap_or_else(|error| panic!("Cannot write fasta record: {error:?}"));
-                        if let Some(hodeco_mapping_writer) = hodeco_mapping_writer.as_mut() {
-                            let hodeco_mapping = hodeco_mapping.unwrap_or_else(|| unreachable!());
-                            hodeco_mapping_writer
-                                .encode(iter::once((id, hodeco_mapping)))
+                    while let Ok((input, output)) = file_receiver.recv() {
+                        let input_file = File::open(input)
+                            .unwrap_or_else(|error| panic!("Cannot open input file: {error:?}"));
+                        let output_file = File::create(&output)
+                            .unwrap_or_else(|error| panic!("Cannot create output file: {error:?}"));
+                        let mut writer = SequenceWriter::new(
+                            BufWriter::new(output_file),
+                            output_format,
+                            output_line_width,
+                        );
+                        for record in read_records(input_file, input_format) {
+                            let record = record
+                                .unwrap_or_else(|error| panic!("Cannot read record: {error:?}"));
+                            if skip_empty && record.seq().is_empty() {
+                                continue;
+                            }
+                            let original = case.apply(record.seq());
+                            let sequence = compression_config.compress(original.iter().cloned());
+                            if min_entropy
+                                .map(|min_entropy| {
+                                    shannon_entropy(sequence.iter().copied()) < min_entropy
+                                })
+                                .unwrap_or(false)
+                            {
+                                continue;
+                            }
+                            writer
+                                .write_record(record.id(), record.desc(), sequence)
                                 .unwrap_or_else(|error| {
-                                    panic!("Error writing hodeco mapping: {error:?}")
+                                    panic!("Cannot write fasta record: {error:?}")
                                 });
                         }
-                    }
-                })
-                .unwrap_or_else(|error| panic!("Cannot spawn output thread: {error:?}"));
-        } else {
-            scope
-                .builder()
-                .name("output_thread".to_string())
-                .spawn(move |_| {
-                    let mut writer = fasta::Writer::new(std::io::stdout());
-                    while let Ok((id, description, (sequence, hodeco_mapping))) =
-                        output_receiver.recv()
-                    {
-                        assert!(
-                            hodeco_mapping.is_none(),
-                            "Found hodeco mapping even though no output file was specified."
-                        );
                         writer
-                            .write(&id, description.as_deref(), &sequence)
-                            .unwrap_or_else(|error| panic!("Cannot write fasta record: {error:?}"));
+                            .flush()
+                            .unwrap_or_else(|error| panic!("Cannot flush output: {error:?}"));
+                        info!("Compressed {input:?} into {output:?}");
                     }
                 })
-                .unwrap_or_else(|error| panic!("Cannot spawn output thread: {error:?}"));
+                .unwrap_or_else(|error| panic!("Cannot spawn file thread: {error:?}"));
         }
+    })
+    .unwrap_or_else(|error| panic!("Error: {error:?}"));
+}
 
-        for thread_id in 0..configuration.threads {
-            let input_receiver = input_receiver.clone();
-            let output_sender = output_sender.clone();
-            let hodeco_map_output = configuration.hodeco_map_output.clone();
-            scope
-                .builder()
-                .name(format!("compute_thread_{thread_id}"))
-                .spawn(move |_| {
-                    if hodeco_map_output.is_some() {
-                        while let Ok(record) = input_receiver.recv() {
-                            let (hoco_sequence, mut hodeco_mapping): (Vec<u8>, Vec<_>) =
-                                homopolymer_compress_with_hodeco_map(record.seq().iter().cloned())
-                                    .unzip();
-                            hodeco_mapping.push(record.seq().len());
-                            output_sender
-                                .send((
-                                    record.id().to_owned(),
-                                    record.desc().map(str::to_owned),
-                                    (hoco_sequence, Some(hodeco_mapping)),
-                                ))
-                                .unwrap_or_else(|error| {
-                                    panic!("Cannot send fasta record: {error:?}")
-                                });
-                        }
-                    } else {
-                        while let Ok(record) = input_receiver.recv() {
-                            let hoco_sequence: Vec<u8> =
-                                homopolymer_compress(record.seq().iter().cloned()).collect();
-                            output_sender
-                                .send((
-                                    record.id().to_owned(),
-                                    record.desc().map(str::to_owned),
-                                    (hoco_sequence, None),
-                                ))
-                                .unwrap_or_else(|error| {
-                                    panic!("Cannot send fasta record: {error:?}")
-                                });
-                        }
+/// The number of bases of the prefix of the input that is used by `--autotune`.
+const AUTOTUNE_SAMPLE_LEN: usize = 16 * 1024 * 1024;
+
+/// The numbers of compute threads tried by `--autotune`.
+const AUTOTUNE_THREADS: [usize; 4] = [1, 2, 4, 8];
+
+/// The memory target of `--auto-config` if `--max-memory` is not given.
+const AUTO_CONFIG_MEMORY: usize = 1024 * 1024 * 1024;
+
+/// Size the buffers of the given pipeline such that the records buffered between its threads fit into its memory limit, or into [`AUTO_CONFIG_MEMORY`].
+/// The buffer sizes are estimated from the mean size of the given sample records, and the memory limit is set such that it holds even for larger records.
+/// Records that are read but not yet written are either in one of the two buffers or in one of the threads.
+fn auto_config_buffers<'sample>(
+    sample: impl Iterator<Item = &'sample fasta::Record>,
+    input_len: Option<u64>,
+    pipeline_config: &mut PipelineConfig,
+) {
+    let (records, bytes) = sample.fold((0usize, 0usize), |(records, bytes), record| {
+        (
+            records + 1,
+            bytes
+                + record.id().len()
+                + record.desc().map(str::len).unwrap_or(0)
+                + record.seq().len(),
+        )
+    });
+    let memory = *pipeline_config.max_memory.get_or_insert(AUTO_CONFIG_MEMORY);
+    let mean_record_bytes = bytes.checked_div(records).unwrap_or(0).max(1);
+    let records_in_flight = memory / mean_record_bytes;
+    let buffer_size =
+        (records_in_flight.saturating_sub(pipeline_config.threads + 2) / 2).clamp(1, 32768);
+    pipeline_config.input_buffer_size = buffer_size;
+    pipeline_config.output_buffer_size = buffer_size;
+
+    // Extrapolate from the size of the input file, which overestimates slightly, since line terminators and `>` are not part of the records.
+    let estimated_records = input_len
+        .filter(|_| bytes > 0)
+        .map(|input_len| (input_len as f64 * records as f64 / bytes as f64).round() as u64);
+    info!(
+        "Auto-config sampled {records} records with a mean size of {mean_record_bytes} bytes{}, and chose --threads {} --buffer-size {buffer_size} --max-memory {memory}",
+        estimated_records
+            .map(|estimated_records| format!(", estimating about {estimated_records} records in total"))
+            .unwrap_or_default(),
+        pipeline_config.threads
+    );
+}
+
+/// Compress the given sample records with each of [`AUTOTUNE_THREADS`] compute threads, and return the fastest number of threads.
+/// The compressed sequences are discarded.
+fn autotune_threads<'sample>(
+    sample: impl Iterator<Item = &'sample fasta::Record> + Clone + Send,
+    pipeline_config: &PipelineConfig,
+    case: Case,
+) -> usize {
+    let mut best = (Duration::MAX, 1);
+    for threads in AUTOTUNE_THREADS {
+        let pipeline_config = PipelineConfig {
+            threads,
+            ..pipeline_config.clone()
+        };
+        let start = Instant::now();
+        run_pipeline(
+            sample.clone().cloned().map(Ok),
+            &pipeline_config,
+            |record| {
+                pipeline_config
+                    .compression
+                    .compress(case.apply(record.seq()).iter().cloned())
+            },
+            &mut |_: Vec<u8>| Ok(()),
+        )
+        .unwrap_or_else(|error| panic!("Error: {error:?}"));
+        let duration = start.elapsed();
+        debug!("Compressing the autotune sample with {threads} compute threads took {duration:?}");
+        if duration < best.0 {
+            best = (duration, threads);
+        }
+    }
+    info!("Autotune chose {} compute threads", best.1);
+    best.1
+}
+
+/// Compress the given records with compute threads, writing the compressed sequences and all requested side outputs.
+/// Returns the statistics of the pipeline, the summary for `--report` and the number of records skipped because of `--skip-errors`.
+fn compress_records(
+    configuration: &Configuration,
+    pipeline_config: &PipelineConfig,
+    records: impl Iterator<Item = std::io::Result<fasta::Record>> + Send,
+) -> (Stats, RunReport, usize) {
+    let compression_config = &pipeline_config.compression;
+    let case = configuration.case;
+    let hodeco_format = configuration.hodeco_format;
+    let concat_index = configuration.concat_index.as_ref().map(|path| {
+        BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create concat index file: {error:?}")),
+        )
+    });
+    let output: Option<Box<dyn Write + Send>> = if let Some(output) = &configuration.output {
+        Some(Box::new(create_output_file(output, configuration.resume)))
+    } else if configuration.hodeco_map_output.is_some() {
+        info!("Only outputting the hodeco map, since no output file was specified");
+        None
+    } else {
+        Some(Box::new(std::io::stdout()))
+    };
+
+    let (mut writer, mut concat_writer) = if let Some(concat_index) = concat_index {
+        let concat_writer = output.map(|output| {
+            ConcatWriter::new(
+                BufWriter::new(output),
+                concat_index,
+                configuration.concat_separator,
+            )
+            .unwrap_or_else(|error| panic!("Cannot write concat index header: {error:?}"))
+        });
+        (None, concat_writer)
+    } else {
+        let output_line_width = configuration.output_line_width;
+        let output_format = configuration.output_format;
+        (
+            output.map(|output| {
+                SequenceWriter::new(BufWriter::new(output), output_format, output_line_width)
+            }),
+            None,
+        )
+    };
+    let hodeco_map_file =
+        configuration.hodeco_map_output.as_ref().map(|path| {
+            BufWriter::new(File::create(path).unwrap_or_else(|error| {
+                panic!("Cannot create hodeco mapping output file: {error:?}")
+            }))
+        });
+    let (mut hodeco_mapping_writer, mut packed_hodeco_map_writer) = match hodeco_format {
+        HodecoFormat::Cbor => (hodeco_map_file.map(Encoder::from_writer), None),
+        HodecoFormat::Packed => (
+            None,
+            hodeco_map_file.map(|file| {
+                PackedHodecoMapWriter::new(file).unwrap_or_else(|error| {
+                    panic!("Cannot write packed hodeco map header: {error:?}")
+                })
+            }),
+        ),
+    };
+    let mut runs_tsv_writer = configuration.runs_tsv.as_ref().map(|path| {
+        let mut runs_tsv_writer = BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create runs tsv output file: {error:?}")),
+        );
+        writeln!(runs_tsv_writer, "record_id\trun_index\tbase\tstart\tlength")
+            .unwrap_or_else(|error| panic!("Cannot write runs tsv header: {error:?}"));
+        runs_tsv_writer
+    });
+    let mut bedgraph_writer = configuration.bedgraph.as_ref().map(|path| {
+        let mut bedgraph_writer = BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create bedgraph output file: {error:?}")),
+        );
+        writeln!(bedgraph_writer, "track type=bedGraph")
+            .unwrap_or_else(|error| panic!("Cannot write bedgraph header: {error:?}"));
+        bedgraph_writer
+    });
+    let mut long_runs_bed_writer = configuration.long_runs_bed.as_ref().map(|path| {
+        BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create long runs bed file: {error:?}")),
+        )
+    });
+    let mut additional_outputs: Vec<_> = configuration
+        .also_output
+        .iter()
+        .map(|path| AdditionalOutput::create(path, configuration.output_line_width))
+        .collect();
+    let mut debug_pairs_writer = configuration.debug_pairs.as_ref().map(|path| {
+        FastaWriter::new(
+            BufWriter::new(
+                File::create(path)
+                    .unwrap_or_else(|error| panic!("Cannot create debug pairs file: {error:?}")),
+            ),
+            configuration.output_line_width,
+        )
+    });
+    let mut cigar_writer = configuration.cigar.as_ref().map(|path| {
+        BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create cigar output file: {error:?}")),
+        )
+    });
+    // The output order depends on the scheduling of the compute threads, so the lines are collected and sorted before writing.
+    let mut dump_map_lines = configuration.dump_map.as_ref().map(|_| Vec::new());
+    let mut sketch_writer = configuration.sketch_output.as_ref().map(|path| {
+        BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create sketch output file: {error:?}")),
+        )
+    });
+    let mut gc_content_writer = configuration.gc_content.as_ref().map(|path| {
+        let mut gc_content_writer = BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create gc content output file: {error:?}")),
+        );
+        writeln!(gc_content_writer, "record_id\tgc_content")
+            .unwrap_or_else(|error| panic!("Cannot write gc content header: {error:?}"));
+        gc_content_writer
+    });
+    let mut longest_run_writer = configuration.report_longest_run.as_ref().map(|path| {
+        let mut longest_run_writer =
+            BufWriter::new(File::create(path).unwrap_or_else(|error| {
+                panic!("Cannot create longest run output file: {error:?}")
+            }));
+        writeln!(longest_run_writer, "record_id\tsymbol\tlength\tstart")
+            .unwrap_or_else(|error| panic!("Cannot write longest run header: {error:?}"));
+        longest_run_writer
+    });
+    // A BTreeMap sorts the histogram by symbol and run length,
+    // such that the output does not depend on the number of threads or on scheduling.
+    let mut histogram = BTreeMap::<(u8, usize), usize>::new();
+
+    let min_entropy = configuration.min_entropy;
+    let no_compress = configuration.no_compress;
+    let compare_baseline = configuration.compare_baseline;
+    let mut compressed_len = 0usize;
+    let mut baseline_len = 0usize;
+    let report_longest_run = configuration.report_longest_run.is_some();
+    let report_gc_content = configuration.gc_content.is_some();
+    let sketch_size = configuration.sketch;
+    let sketch_k = configuration.sketch_k;
+    let output_debug_pairs = configuration.debug_pairs.is_some();
+    let trace_records = configuration.trace_records;
+    let ascii_only = configuration.ascii_only;
+    let annotate_ratio = configuration.annotate_ratio;
+    let low_entropy_records = AtomicUsize::new(0);
+    // The total length of the original sequences of records dropped in the compute threads.
+    let dropped_len = AtomicUsize::new(0);
+    let length_range = configuration.length_range;
+    let out_of_range_records = AtomicUsize::new(0);
+    let skip_errors = configuration.skip_errors;
+    let skipped_error_records = AtomicUsize::new(0);
+    let compute_hodeco_mapping = configuration.hodeco_map_output.is_some();
+    let output_run_lengths = configuration.runs_tsv.is_some()
+        || configuration.bedgraph.is_some()
+        || configuration.long_runs_bed.is_some()
+        || configuration.cigar.is_some()
+        || configuration.dump_map.is_some();
+    let compute_histogram = configuration.run_length_histogram.is_some();
+    let mut run_quantiles = configuration.run_quantiles.then(RunLengthQuantiles::new);
+    let mut most_compressible = configuration.top_compressible.map(MostCompressible::new);
+    let compute_runs = output_run_lengths || compute_histogram || run_quantiles.is_some();
+    let hodeco_checksum = configuration
+        .hodeco_checksum
+        .filter(|_| compute_hodeco_mapping);
+
+    let stats = run_pipeline(
+        records,
+        pipeline_config,
+        |record| {
+            let start_time = trace_records.then(Instant::now);
+            if let Some((min_len, max_len)) = length_range {
+                if !(min_len..=max_len).contains(&record.seq().len()) {
+                    out_of_range_records.fetch_add(1, Ordering::Relaxed);
+                    dropped_len.fetch_add(record.seq().len(), Ordering::Relaxed);
+                    return None;
+                }
+            }
+            if ascii_only {
+                if let Some(offset) = record
+                    .seq()
+                    .iter()
+                    .position(|byte| !byte.is_ascii_graphic())
+                {
+                    let message = format!(
+                        "Record {} contains the byte {:#04x} at offset {offset}, which is not printable ASCII",
+                        record.id(),
+                        record.seq()[offset]
+                    );
+                    if !skip_errors {
+                        panic!("{message}");
                     }
+                    warn!("{message}, skipping it");
+                    skipped_error_records.fetch_add(1, Ordering::Relaxed);
+                    dropped_len.fetch_add(record.seq().len(), Ordering::Relaxed);
+                    return None;
+                }
+            }
+            let original = case.apply(record.seq());
+            let (sequence, hodeco_map) = if no_compress {
+                (record.seq().to_vec(), None)
+            } else if compute_hodeco_mapping {
+                // The map needs the number of original items of each compressed item, which splits runs that are shortened to multiple items.
+                let (sequence, run_lengths): (Vec<u8>, Vec<_>) = compression_config
+                    .compress_with_run_lengths(original.iter().cloned())
+                    .unzip();
+                let hodeco_map = HodecoMap::from_run_lengths(
+                    record.id().to_owned(),
+                    run_lengths,
+                    hodeco_checksum.map(|algorithm| Checksum::compute(algorithm, &original)),
+                );
+                (sequence, Some(hodeco_map))
+            } else {
+                (compression_config.compress(original.iter().cloned()), None)
+            };
+            // The run outputs describe the runs of the original sequence, independently of which runs are compressed and how.
+            let runs = compute_runs.then(|| {
+                homopolymer_compress_with_run_lengths(original.iter().cloned()).collect::<Vec<_>>()
+            });
+            if let Some(start_time) = start_time {
+                debug!(
+                    "Thread {} processed record {} of length {} in {:?}",
+                    std::thread::current().name().unwrap_or("unnamed"),
+                    record.id(),
+                    record.seq().len(),
+                    start_time.elapsed()
+                );
+            }
+            if min_entropy
+                .map(|min_entropy| shannon_entropy(sequence.iter().copied()) < min_entropy)
+                .unwrap_or(false)
+            {
+                low_entropy_records.fetch_add(1, Ordering::Relaxed);
+                dropped_len.fetch_add(record.seq().len(), Ordering::Relaxed);
+                return None;
+            }
+            let description = if annotate_ratio {
+                let ratio = if record.seq().is_empty() {
+                    1.0
+                } else {
+                    sequence.len() as f64 / record.seq().len() as f64
+                };
+                Some(if let Some(description) = record.desc() {
+                    format!("{description} hoco_ratio={ratio:.2}")
+                } else {
+                    format!("hoco_ratio={ratio:.2}")
                 })
-                .unwrap_or_else(|error| panic!("Cannot spawn compute thread: {error:?}"));
-        }
-    })
+            } else {
+                record.desc().map(str::to_owned)
+            };
+            let sketch = sketch_size.map(|size| bottom_k_sketch(&sequence, sketch_k, size));
+            Some(CompressedRecord {
+                id: record.id().to_owned(),
+                description,
+                sequence,
+                original_len: record.seq().len(),
+                hodeco_map,
+                longest_run: report_longest_run
+                    .then(|| longest_run(original.iter().copied()))
+                    .flatten(),
+                gc_content: report_gc_content
+                    .then(|| compressed_gc_content(original.iter().copied())),
+                sketch,
+                original: output_debug_pairs.then(|| record.seq().to_vec()),
+                runs,
+                baseline_len: compare_baseline
+                    .map(|min_run| homopolymer_compress_clamp(&original, min_run, 1).len()),
+            })
+        },
+        &mut |record| {
+            let CompressedRecord {
+                id,
+                description,
+                sequence,
+                original_len,
+                hodeco_map,
+                longest_run,
+                gc_content,
+                sketch,
+                original,
+                runs,
+                baseline_len: record_baseline_len,
+            } = if let Some(record) = record {
+                record
+            } else {
+                return Ok(());
+            };
+            if let Some(writer) = writer.as_mut() {
+                writer
+                    .write_record(&id, description.as_deref(), sequence.iter().copied())
+                    .unwrap_or_else(|error| panic!("Cannot write fasta record: {error:?}"));
+            }
+            for additional_output in &mut additional_outputs {
+                additional_output
+                    .write_record(&id, description.as_deref(), &sequence)
+                    .unwrap_or_else(|error| {
+                        panic!("Cannot write record to additional output: {error:?}")
+                    });
+            }
+            if let Some(concat_writer) = concat_writer.as_mut() {
+                concat_writer
+                    .write_record(&id, &sequence)
+                    .unwrap_or_else(|error| panic!("Cannot write concatenated record: {error:?}"));
+            }
+            compressed_len += sequence.len();
+            if let Some(record_baseline_len) = record_baseline_len {
+                baseline_len += record_baseline_len;
+            }
+            if let Some(most_compressible) = most_compressible.as_mut() {
+                most_compressible.add(&id, original_len, sequence.len());
+            }
+            if let Some(run_quantiles) = run_quantiles.as_mut() {
+                let runs = runs.as_ref().unwrap_or_else(|| unreachable!());
+                for &(_, length) in runs {
+                    run_quantiles.add(length);
+                }
+            }
+            if compute_histogram {
+                let runs = runs.as_ref().unwrap_or_else(|| unreachable!());
+                for &(base, length) in runs {
+                    *histogram.entry((base, length)).or_insert(0) += 1;
+                }
+            }
+            if let Some(debug_pairs_writer) = debug_pairs_writer.as_mut() {
+                let original = original.unwrap_or_else(|| unreachable!());
+                debug_pairs_writer
+                    .write_record(&format!("{id}.orig"), description.as_deref(), original)
+                    .and_then(|()| {
+                        debug_pairs_writer.write_record(
+                            &format!("{id}.hoco"),
+                            description.as_deref(),
+                            sequence.iter().copied(),
+                        )
+                    })
+                    .unwrap_or_else(|error| panic!("Cannot write debug pair: {error:?}"));
+            }
+            if let (Some(longest_run_writer), Some((symbol, length, start))) =
+                (longest_run_writer.as_mut(), longest_run)
+            {
+                writeln!(
+                    longest_run_writer,
+                    "{id}\t{}\t{length}\t{start}",
+                    char::from(symbol)
+                )
+                .unwrap_or_else(|error| panic!("Cannot write longest run row: {error:?}"));
+            }
+            if let (Some(gc_content_writer), Some(gc_content)) =
+                (gc_content_writer.as_mut(), gc_content)
+            {
+                writeln!(gc_content_writer, "{id}\t{gc_content:.4}")
+                    .unwrap_or_else(|error| panic!("Cannot write gc content row: {error:?}"));
+            }
+            if let (Some(sketch_writer), Some(sketch)) = (sketch_writer.as_mut(), sketch) {
+                let sketch: Vec<_> = sketch.iter().map(u64::to_string).collect();
+                writeln!(sketch_writer, "{id}\t{}", sketch.join(","))
+                    .unwrap_or_else(|error| panic!("Cannot write sketch: {error:?}"));
+            }
+            if let Some(cigar_writer) = cigar_writer.as_mut() {
+                let runs = runs.as_ref().unwrap_or_else(|| unreachable!());
+                writeln!(cigar_writer, "{id}\t{}", to_cigar(runs.iter().copied()))
+                .unwrap_or_else(|error| panic!("Cannot write cigar: {error:?}"));
+            }
+            if let Some(dump_map_lines) = dump_map_lines.as_mut() {
+                let runs = runs.as_ref().unwrap_or_else(|| unreachable!());
+                let mut line = format!("{id}:");
+                for (_, run_length) in runs {
+                    line.push(' ');
+                    line.push_str(&run_length.to_string());
+                }
+                dump_map_lines.push(line);
+            }
+            if let Some(bedgraph_writer) = bedgraph_writer.as_mut() {
+                let runs = runs.as_ref().unwrap_or_else(|| unreachable!());
+                let mut start = 0;
+                for (_, length) in runs {
+                    writeln!(
+                        bedgraph_writer,
+                        "{id}\t{start}\t{}\t{length}",
+                        start + length
+                    )
+                    .unwrap_or_else(|error| panic!("Cannot write bedgraph row: {error:?}"));
+                    start += length;
+                }
+            }
+            if let Some(long_runs_bed_writer) = long_runs_bed_writer.as_mut() {
+                let runs = runs.as_ref().unwrap_or_else(|| unreachable!());
+                for (base, range) in long_runs(runs.iter().copied(), configuration.long_runs_min_length)
+                {
+                    writeln!(
+                        long_runs_bed_writer,
+                        "{id}\t{}\t{}\t{}\t{}",
+                        range.start,
+                        range.end,
+                        char::from(base),
+                        range.len()
+                    )
+                    .unwrap_or_else(|error| panic!("Cannot write long runs bed row: {error:?}"));
+                }
+            }
+            if let Some(runs_tsv_writer) = runs_tsv_writer.as_mut() {
+                let runs = runs.unwrap_or_else(|| unreachable!());
+                let mut start = 0;
+                for (run_index, (base, length)) in runs.into_iter().enumerate() {
+                    writeln!(
+                        runs_tsv_writer,
+                        "{id}\t{run_index}\t{}\t{start}\t{length}",
+                        char::from(base)
+                    )
+                    .unwrap_or_else(|error| panic!("Cannot write runs tsv row: {error:?}"));
+                    start += length;
+                }
+            }
+            if let Some(hodeco_mapping_writer) = hodeco_mapping_writer.as_mut() {
+                let hodeco_map = hodeco_map.unwrap_or_else(|| unreachable!());
+                let hodeco_mapping = hodeco_map.mapping();
+                if let Some(checksum) = hodeco_map.checksum {
+                    hodeco_mapping_writer.encode(iter::once((
+                        id,
+                        hodeco_mapping,
+                        checksum.algorithm.to_string(),
+                        checksum.value,
+                    )))
+                } else {
+                    hodeco_mapping_writer.encode(iter::once((id, hodeco_mapping)))
+                }
+                .unwrap_or_else(|error| panic!("Error writing hodeco mapping: {error:?}"));
+            } else if let Some(packed_hodeco_map_writer) = packed_hodeco_map_writer.as_mut() {
+                let hodeco_map = hodeco_map.unwrap_or_else(|| unreachable!());
+                packed_hodeco_map_writer
+                    .write(&hodeco_map)
+                    .unwrap_or_else(|error| panic!("Error writing hodeco mapping: {error:?}"));
+            }
+            Ok(())
+        },
+    )
     .unwrap_or_else(|error| panic!("Error: {error:?}"));
+
+    if let Some(min_run) = compare_baseline {
+        info!(
+            "Compressed to {compressed_len} bytes, while only collapsing runs of at least {min_run} items gives {baseline_len} bytes, which are {} bytes more",
+            baseline_len as isize - compressed_len as isize
+        );
+    }
+    let low_entropy_records = low_entropy_records.into_inner();
+    if min_entropy.is_some() {
+        info!("Dropped {low_entropy_records} records with low entropy");
+    }
+    let out_of_range_records = out_of_range_records.into_inner();
+    if let Some((min_len, max_len)) = length_range {
+        info!("Dropped {out_of_range_records} records with a length outside of {min_len} to {max_len}");
+    }
+    if let Some(writer) = writer.as_mut() {
+        writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush output: {error:?}"));
+    }
+    if let Some(packed_hodeco_map_writer) = packed_hodeco_map_writer {
+        packed_hodeco_map_writer
+            .finish()
+            .unwrap_or_else(|error| panic!("Cannot finish hodeco map: {error:?}"));
+    }
+    for additional_output in additional_outputs {
+        additional_output
+            .finish()
+            .unwrap_or_else(|error| panic!("Cannot finish additional output: {error:?}"));
+    }
+    if let Some(concat_writer) = concat_writer.as_mut() {
+        concat_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush concat output: {error:?}"));
+    }
+    if let Some(debug_pairs_writer) = debug_pairs_writer.as_mut() {
+        debug_pairs_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush debug pairs: {error:?}"));
+    }
+    if let Some(longest_run_writer) = longest_run_writer.as_mut() {
+        longest_run_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush longest run output: {error:?}"));
+    }
+    if let Some(gc_content_writer) = gc_content_writer.as_mut() {
+        gc_content_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush gc content output: {error:?}"));
+    }
+    if let Some(sketch_writer) = sketch_writer.as_mut() {
+        sketch_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush sketch output: {error:?}"));
+    }
+    if let Some(cigar_writer) = cigar_writer.as_mut() {
+        cigar_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush cigar output: {error:?}"));
+    }
+    if let (Some(path), Some(mut dump_map_lines)) = (&configuration.dump_map, dump_map_lines) {
+        dump_map_lines.sort_unstable();
+        let mut writer = BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create dump map file: {error:?}")),
+        );
+        for line in &dump_map_lines {
+            writeln!(writer, "{line}")
+                .unwrap_or_else(|error| panic!("Cannot write dump map: {error:?}"));
+        }
+        writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush dump map: {error:?}"));
+    }
+    if let Some(bedgraph_writer) = bedgraph_writer.as_mut() {
+        bedgraph_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush bedgraph: {error:?}"));
+    }
+    if let Some(long_runs_bed_writer) = long_runs_bed_writer.as_mut() {
+        long_runs_bed_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush long runs bed: {error:?}"));
+    }
+    if let Some(runs_tsv_writer) = runs_tsv_writer.as_mut() {
+        runs_tsv_writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush runs tsv: {error:?}"));
+    }
+    if let Some(path) = &configuration.run_length_histogram {
+        let mut writer =
+            BufWriter::new(File::create(path).unwrap_or_else(|error| {
+                panic!("Cannot create run length histogram file: {error:?}")
+            }));
+        writeln!(writer, "symbol\trun_length\tcount")
+            .and_then(|()| {
+                for ((symbol, run_length), count) in histogram {
+                    writeln!(writer, "{}\t{run_length}\t{count}", char::from(symbol))?;
+                }
+                writer.flush()
+            })
+            .unwrap_or_else(|error| panic!("Cannot write run length histogram: {error:?}"));
+    }
+    if let Some(most_compressible) = most_compressible {
+        eprintln!("Most compressible records (id and compression factor):");
+        for (id, factor) in most_compressible.into_sorted_vec() {
+            eprintln!("{id}\t{factor:.3}");
+        }
+    }
+    if let Some(run_quantiles) = run_quantiles {
+        let [p50, p90, p99] = [0.5, 0.9, 0.99].map(|q| {
+            run_quantiles
+                .quantile(q)
+                .map(|length| length.to_string())
+                .unwrap_or_else(|| "none".to_owned())
+        });
+        info!(
+            "Run length percentiles of {} runs: p50 = {p50}, p90 = {p90}, p99 = {p99}",
+            run_quantiles.runs()
+        );
+    }
+    let skipped_error_records = skipped_error_records.into_inner();
+    let mut report = RunReport::from_stats(&stats);
+    report.drop_processed(
+        skipped_error_records + low_entropy_records + out_of_range_records,
+        dropped_len.into_inner(),
+    );
+    report.output_sequence_bytes = compressed_len;
+    (stats, report, skipped_error_records)
+}
+
+fn main() {
+    let start = Instant::now();
+    let mut configuration = Configuration::parse();
+    initialise_logging(configuration.log_format);
+    if configuration.hodeco_map.is_some() {
+        configuration.hodeco_map_output = configuration.hodeco_map.take();
+    }
+
+    if !configuration.inputs.is_empty() {
+        compress_files_in_parallel(&configuration);
+        return;
+    }
+    if let Some(sequence) = &configuration.sequence {
+        compress_sequence(&configuration, sequence);
+        return;
+    }
+
+    let input = configuration
+        .input
+        .clone()
+        .unwrap_or_else(|| unreachable!());
+    check_input_extension(&input, configuration.input_format);
+    if configuration.stream {
+        compress_streaming(&configuration, &input);
+        return;
+    }
+
+    let atomic_outputs = configuration
+        .atomic
+        .then(|| AtomicOutputs::redirect(&mut configuration));
+    let mut pipeline_config = configuration.pipeline_config();
+    let compression_config = &configuration.compression_config();
+    let case = configuration.case;
+    let skip_empty = configuration.skip_empty;
+    let mut preview = configuration.preview;
+    let records = if let Some(region) = &configuration.region {
+        if configuration.input_format != InputFormat::Fasta {
+            panic!("Regions are only supported for fasta input");
+        }
+        Box::new(iter::once(Ok(read_region(
+            &input,
+            configuration.fai.as_deref(),
+            region,
+        ))))
+    } else {
+        let input_file =
+            File::open(&input).unwrap_or_else(|error| panic!("Cannot open input file: {error:?}"));
+        read_records(input_file, configuration.input_format)
+    };
+    // Lines of plain input are independent, so reading can continue after a line that is not valid UTF-8.
+    let skipped_input_records = Arc::new(AtomicUsize::new(0));
+    let records: Box<dyn Iterator<Item = std::io::Result<fasta::Record>> + Send> =
+        if configuration.skip_errors && configuration.input_format == InputFormat::Plain {
+            let skipped_input_records = Arc::clone(&skipped_input_records);
+            Box::new(records.filter(move |record| match record {
+                Err(error) if error.kind() == ErrorKind::InvalidData => {
+                    warn!("{error}, skipping it");
+                    skipped_input_records.fetch_add(1, Ordering::Relaxed);
+                    false
+                }
+                _ => true,
+            }))
+        } else {
+            records
+        };
+    let records: Box<dyn Iterator<Item = std::io::Result<fasta::Record>> + Send> =
+        if configuration.resume {
+            let written_ids = prepare_resume(
+                configuration
+                    .output
+                    .as_ref()
+                    .unwrap_or_else(|| unreachable!()),
+            );
+            Box::new(records.filter(move |record| {
+                record
+                    .as_ref()
+                    .map(|record| !written_ids.contains(record.id()))
+                    .unwrap_or(true)
+            }))
+        } else {
+            records
+        };
+    let records = records.inspect(move |record| {
+        if let Ok(record) = record {
+            if skip_empty && record.seq().is_empty() {
+                return;
+            }
+            if let Some(preview) = preview.take() {
+                let runs: Vec<_> =
+                    homopolymer_compress_with_run_lengths(case.apply(record.seq()).iter().cloned())
+                        .take(preview)
+                        .map(|(base, length)| format!("{}×{length}", char::from(base)))
+                        .collect();
+                eprintln!("Preview of {}: {}", record.id(), runs.join(" "));
+            }
+        }
+    });
+
+    let records: Box<dyn Iterator<Item = std::io::Result<fasta::Record>> + Send> =
+        if configuration.autotune || configuration.auto_config {
+            let mut records = records;
+            let mut sample = Vec::new();
+            let mut sample_len = 0;
+            while sample_len < AUTOTUNE_SAMPLE_LEN {
+                if let Some(record) = records.next() {
+                    if let Ok(record) = &record {
+                        sample_len += record.seq().len();
+                    } else {
+                        // Let the pipeline report the error.
+                        sample.push(record);
+                        break;
+                    }
+                    sample.push(record);
+                } else {
+                    break;
+                }
+            }
+            pipeline_config.threads = autotune_threads(
+                sample.iter().filter_map(|record| record.as_ref().ok()),
+                &pipeline_config,
+                case,
+            );
+            if configuration.auto_config {
+                auto_config_buffers(
+                    sample.iter().filter_map(|record| record.as_ref().ok()),
+                    std::fs::metadata(&input)
+                        .ok()
+                        .map(|metadata| metadata.len()),
+                    &mut pipeline_config,
+                );
+            }
+            Box::new(sample.into_iter().chain(records))
+        } else {
+            Box::new(records)
+        };
+
+    let (stats, mut report, skipped_error_records) = if configuration.stream_output {
+        let output: Box<dyn Write + Send> = if let Some(output) = &configuration.output {
+            Box::new(create_output_file(output, configuration.resume))
+        } else {
+            Box::new(std::io::stdout())
+        };
+        let mut writer = SequenceWriter::new(
+            BufWriter::new(output),
+            configuration.output_format,
+            configuration.output_line_width,
+        );
+        let pipeline_config = PipelineConfig {
+            threads: 0,
+            ..pipeline_config.clone()
+        };
+        let mut compressed_len = 0;
+        let stats = run_pipeline(
+            records,
+            &pipeline_config,
+            |record| record,
+            &mut |record: fasta::Record| {
+                writer.write_record(
+                    record.id(),
+                    record.desc(),
+                    compression_config
+                        .compress_with_run_lengths(case.apply(record.seq()).iter().cloned())
+                        .map(|(item, _)| item)
+                        .inspect(|_| compressed_len += 1),
+                )
+            },
+        )
+        .unwrap_or_else(|error| panic!("Error: {error:?}"));
+        writer
+            .flush()
+            .unwrap_or_else(|error| panic!("Cannot flush output: {error:?}"));
+        let mut report = RunReport::from_stats(&stats);
+        report.output_sequence_bytes = compressed_len;
+        (stats, report, 0)
+    } else {
+        compress_records(&configuration, &pipeline_config, records)
+    };
+    if let Some(atomic_outputs) = atomic_outputs {
+        atomic_outputs.commit();
+    }
+    let skipped_input_records = skipped_input_records.load(Ordering::Relaxed);
+    report.skip_unread(skipped_input_records);
+    if let Some(path) = &configuration.report {
+        let mut writer = BufWriter::new(
+            File::create(path)
+                .unwrap_or_else(|error| panic!("Cannot create report file: {error:?}")),
+        );
+        report
+            .write_json(&mut writer, start.elapsed())
+            .and_then(|()| writer.flush())
+            .unwrap_or_else(|error| panic!("Cannot write report: {error:?}"));
+    }
+    let skipped_error_records = skipped_error_records + skipped_input_records;
+
+    if skip_empty {
+        info!(
+            "Skipped {} records with empty sequences",
+            stats.skipped_empty_records
+        );
+    }
+    if skipped_error_records > 0 {
+        warn!("Skipped {skipped_error_records} records with errors");
+        std::process::exit(2);
+    }
 }
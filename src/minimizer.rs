@@ -0,0 +1,191 @@
+//! Minimizers and homopolymer compression that preserves them.
+
+use std::collections::{BTreeSet, VecDeque};
+
+/// The base of the polynomial rolling hash of [`kmer_hashes`], which is the 64-bit FNV prime.
+const ROLLING_HASH_BASE: u64 = 0x0000_0100_0000_01b3;
+
+/// The finaliser of the 64-bit MurmurHash3, which mixes all bits of the input into all bits of the output.
+fn fmix64(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// Compute the hashes of all k-mers of the given sequence, in the order of their start positions.
+///
+/// Each k-mer is hashed with a polynomial rolling hash modulo 2^64, with the bytes as coefficients and [`ROLLING_HASH_BASE`] as base,
+/// such that the hash of the next k-mer is computed from the hash of the previous one in constant time.
+/// The rolling hash is then mixed with the finaliser of the 64-bit MurmurHash3, such that the hashes are spread uniformly.
+/// The hashes are strand-specific, i.e. a k-mer and its reverse complement have unrelated hashes.
+///
+/// Panics if `k` is zero.
+pub fn kmer_hashes(sequence: &[u8], k: usize) -> impl '_ + Iterator<Item = u64> {
+    assert!(k > 0, "k must be positive");
+    // The weight of the byte that leaves the k-mer when rolling it forward.
+    let leaving_weight = (1..k).fold(1u64, |weight, _| weight.wrapping_mul(ROLLING_HASH_BASE));
+    let mut hash = 0u64;
+    sequence
+        .iter()
+        .enumerate()
+        .filter_map(move |(position, &byte)| {
+            if position >= k {
+                hash = hash
+                    .wrapping_sub(leaving_weight.wrapping_mul(u64::from(sequence[position - k])));
+            }
+            hash = hash
+                .wrapping_mul(ROLLING_HASH_BASE)
+                .wrapping_add(u64::from(byte));
+            (position + 1 >= k).then(|| fmix64(hash))
+        })
+}
+
+/// Compute the bottom-`size` MinHash sketch of the given sequence, i.e. the `size` smallest distinct hashes of its k-mers, in increasing order.
+///
+/// The k-mers are hashed with [`kmer_hashes`].
+/// The sketch has fewer than `size` hashes if the sequence has fewer distinct k-mer hashes.
+/// The similarity of two sequences can be estimated from the overlap of their sketches.
+///
+/// Panics if `k` is zero.
+pub fn bottom_k_sketch(sequence: &[u8], k: usize, size: usize) -> Vec<u64> {
+    let mut sketch = BTreeSet::new();
+    for hash in kmer_hashes(sequence, k) {
+        if sketch.len() < size {
+            sketch.insert(hash);
+        } else if sketch
+            .iter()
+            .next_back()
+            .map(|&largest| hash < largest)
+            .unwrap_or(false)
+            && sketch.insert(hash)
+        {
+            let largest = *sketch.iter().next_back().unwrap_or_else(|| unreachable!());
+            sketch.remove(&largest);
+        }
+    }
+    sketch.into_iter().collect()
+}
+
+/// Compute the start positions of the minimizers of the given sequence, in increasing order.
+///
+/// For each window of `w` consecutive k-mers, the minimizer is the lexicographically smallest k-mer of the window, where ties are broken by choosing the leftmost k-mer.
+/// Each position is reported once, even if it is the minimizer of multiple windows.
+/// If the sequence has fewer than `w` k-mers, there are no windows and hence no minimizers.
+///
+/// Panics if `k` or `w` is zero.
+pub fn minimizer_positions(sequence: &[u8], k: usize, w: usize) -> Vec<usize> {
+    assert!(k > 0, "k must be positive");
+    assert!(w > 0, "w must be positive");
+    let kmer = |position: usize| &sequence[position..position + k];
+    let kmer_count = (sequence.len() + 1).saturating_sub(k);
+
+    let mut positions = Vec::new();
+    // The candidates for the minimizer of the current window, with increasing positions and non-decreasing k-mers.
+    let mut candidates = VecDeque::new();
+    for position in 0..kmer_count {
+        while candidates
+            .back()
+            .map(|&candidate| kmer(candidate) > kmer(position))
+            .unwrap_or(false)
+        {
+            candidates.pop_back();
+        }
+        candidates.push_back(position);
+        if candidates[0] + w <= position {
+            candidates.pop_front();
+        }
+        if position + 1 >= w && positions.last() != Some(&candidates[0]) {
+            positions.push(candidates[0]);
+        }
+    }
+    positions
+}
+
+/// Homopolymer compress the given sequence, but keep the start positions of all its minimizers.
+///
+/// The minimizers are computed on the original sequence with [`minimizer_positions`].
+/// Each run is replaced by its first item as usual, which is the only item kept from runs without minimizers.
+/// Additionally, each item inside a run that is the start of a minimizer is kept, such that a run can contribute multiple copies of its item to the output.
+/// Minimizers that start at the first item of a run are preserved by the run leader itself, and do not cause extra items.
+///
+/// Panics if `k` or `w` is zero.
+pub fn homopolymer_compress_preserve_minimizers(input: &[u8], k: usize, w: usize) -> Vec<u8> {
+    let mut minimizers = minimizer_positions(input, k, w).into_iter().peekable();
+    input
+        .iter()
+        .enumerate()
+        .filter(|&(position, item)| {
+            let is_minimizer = minimizers.next_if_eq(&position).is_some();
+            position == 0 || input[position - 1] != *item || is_minimizer
+        })
+        .map(|(_, &item)| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::homopolymer_compress;
+    use crate::minimizer::{
+        bottom_k_sketch, homopolymer_compress_preserve_minimizers, kmer_hashes, minimizer_positions,
+    };
+
+    #[test]
+    fn test_minimizer_positions() {
+        // k-mers: CA AT TG GC CA AA
+        assert_eq!(minimizer_positions(b"CATGCAA", 2, 3), [1, 4, 5]);
+        assert_eq!(minimizer_positions(b"CATGCAA", 2, 1), [0, 1, 2, 3, 4, 5]);
+        // The leftmost of equal k-mers is chosen.
+        assert_eq!(minimizer_positions(b"AAAA", 2, 2), [0, 1]);
+        assert!(minimizer_positions(b"ACG", 2, 3).is_empty());
+        assert!(minimizer_positions(b"", 1, 1).is_empty());
+    }
+
+    #[test]
+    fn test_kmer_hashes() {
+        let sequence = b"ACGTTGCAACGT";
+        let hashes: Vec<_> = kmer_hashes(sequence, 4).collect();
+        assert_eq!(hashes.len(), sequence.len() - 3);
+        // The rolling hash equals hashing each k-mer on its own.
+        for (position, &hash) in hashes.iter().enumerate() {
+            assert_eq!(
+                kmer_hashes(&sequence[position..position + 4], 4).collect::<Vec<_>>(),
+                [hash]
+            );
+        }
+        // Equal k-mers have equal hashes.
+        assert_eq!(hashes[0], hashes[8]);
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(kmer_hashes(b"ACG", 4).count(), 0);
+    }
+
+    #[test]
+    fn test_bottom_k_sketch() {
+        let sequence = b"ACGTTGCAACGTACGGT";
+        let mut all_hashes: Vec<_> = kmer_hashes(sequence, 3).collect();
+        all_hashes.sort_unstable();
+        all_hashes.dedup();
+        assert_eq!(bottom_k_sketch(sequence, 3, 5), all_hashes[..5]);
+        assert_eq!(bottom_k_sketch(sequence, 3, 100), all_hashes);
+        assert!(bottom_k_sketch(sequence, 3, 0).is_empty());
+        assert!(bottom_k_sketch(b"AC", 3, 5).is_empty());
+    }
+
+    #[test]
+    fn test_preserve_minimizers() {
+        // k-mers: TA AA AA AA AC CG, minimizers at 1, 2 and 3, where 2 and 3 are inside the run of As.
+        assert_eq!(minimizer_positions(b"TAAAACG", 2, 3), [1, 2, 3]);
+        assert_eq!(
+            homopolymer_compress_preserve_minimizers(b"TAAAACG", 2, 3),
+            b"TAAACG"
+        );
+        // Without a minimizer inside a run, this is plain homopolymer compression.
+        let input = b"ACCGTTA";
+        assert_eq!(
+            homopolymer_compress_preserve_minimizers(input, 3, 10),
+            homopolymer_compress(input.iter().copied()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! Homopolymer compression of multiple sequence alignments.
+
+use crate::homopolymer_compress;
+
+/// Homopolymer compress the columns of the given multiple sequence alignment, where each row is an aligned sequence.
+///
+/// Each column is treated as a single item, such that consecutive columns are collapsed into one only if they are identical in all rows.
+/// Returns the rows of the compressed alignment.
+///
+/// Panics if the rows have different lengths.
+pub fn homopolymer_compress_columns(matrix: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let columns = matrix.first().map(Vec::len).unwrap_or(0);
+    assert!(
+        matrix.iter().all(|row| row.len() == columns),
+        "all rows of the alignment must have the same length"
+    );
+
+    let mut compressed = vec![Vec::new(); matrix.len()];
+    for column in homopolymer_compress(
+        (0..columns).map(|column| matrix.iter().map(|row| row[column]).collect::<Vec<_>>()),
+    ) {
+        for (row, item) in compressed.iter_mut().zip(column) {
+            row.push(item);
+        }
+    }
+    compressed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msa::homopolymer_compress_columns;
+
+    #[test]
+    fn test_compress_columns() {
+        // Only the first two and the last two columns are identical, while the two CC and GG in the first row are not collapsed.
+        let matrix = [
+            b"AACCGGTT-".to_vec(),
+            b"AACTGGTT-".to_vec(),
+            b"AACTGTTT-".to_vec(),
+        ];
+        assert_eq!(
+            homopolymer_compress_columns(&matrix),
+            [
+                b"ACCGGT-".to_vec(),
+                b"ACTGGT-".to_vec(),
+                b"ACTGTT-".to_vec()
+            ]
+        );
+        assert!(homopolymer_compress_columns(&[]).is_empty());
+        assert_eq!(
+            homopolymer_compress_columns(&[Vec::new(), Vec::new()]),
+            [Vec::<u8>::new(), Vec::new()]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Parallel homopolymer compression of a single long sequence.
+
+use crate::homopolymer_compress;
+use crossbeam::thread;
+
+/// Homopolymer compress the given sequence in parallel, splitting it into chunks of roughly `target_chunk` bytes.
+///
+/// Chunks are split only where the symbol changes, i.e. the split after each multiple of `target_chunk` is moved forward to the next run boundary.
+/// Since no run spans two chunks, the chunks can be compressed independently and concatenated without merging at the seams.
+/// This makes the chunks slightly uneven, and a chunk can become arbitrarily large if the sequence contains a very long run.
+///
+/// The number of threads is chosen by the caller, like `--threads` of the command line interface, since detecting the available parallelism requires a newer Rust version than this crate supports.
+/// The chunks are compressed by at most `threads` threads, each of which compresses a contiguous group of chunks, so the number of threads does not grow with the input.
+/// Panics if `target_chunk` or `threads` is zero.
+pub fn homopolymer_compress_par_aligned(
+    input: &[u8],
+    target_chunk: usize,
+    threads: usize,
+) -> Vec<u8> {
+    assert!(target_chunk > 0, "target_chunk must be positive");
+    assert!(threads > 0, "threads must be positive");
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < input.len() {
+        let mut end = start.saturating_add(target_chunk).min(input.len());
+        while end < input.len() && input[end] == input[end - 1] {
+            end += 1;
+        }
+        chunks.push(&input[start..end]);
+        start = end;
+    }
+
+    // Rounding up makes sure that no more than `threads` groups are created.
+    let chunks_per_thread = ((chunks.len() + threads - 1) / threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .chunks(chunks_per_thread)
+            .enumerate()
+            .map(|(thread_id, group)| {
+                scope
+                    .builder()
+                    .name(format!("chunk_thread_{thread_id}"))
+                    .spawn(move |_| {
+                        group
+                            .iter()
+                            .flat_map(|chunk| homopolymer_compress(chunk.iter().copied()))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_else(|error| panic!("Cannot spawn chunk thread: {error:?}"))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|error| panic!("Chunk thread panicked: {error:?}"))
+            })
+            .collect()
+    })
+    .unwrap_or_else(|error| panic!("Error: {error:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::homopolymer_compress;
+    use crate::parallel::homopolymer_compress_par_aligned;
+    use crate::test_util::random_generator;
+
+    #[test]
+    fn test_par_aligned() {
+        let mut random = random_generator(42);
+
+        for _ in 0..100 {
+            let len = random() % 1000;
+            let alphabet_size = 1 + random() % 4;
+            let input: Vec<_> = (0..len)
+                .map(|_| b"ACGT"[random() % alphabet_size])
+                .collect();
+            let target_chunk = 1 + random() % 100;
+            let threads = 1 + random() % 8;
+            let expected: Vec<_> = homopolymer_compress(input.iter().copied()).collect();
+            assert_eq!(
+                homopolymer_compress_par_aligned(&input, target_chunk, threads),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_par_aligned_long_run() {
+        assert_eq!(homopolymer_compress_par_aligned(b"AAAAAAAAAC", 2, 4), b"AC");
+        assert_eq!(homopolymer_compress_par_aligned(b"", 2, 4), b"");
+    }
+
+    #[test]
+    fn test_par_aligned_single_thread() {
+        // Many small chunks are all compressed by the same thread.
+        let input = b"ACGT".repeat(1000);
+        assert_eq!(homopolymer_compress_par_aligned(&input, 1, 1), input);
+    }
+}
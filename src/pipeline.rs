@@ -0,0 +1,912 @@
+//! The multi-threaded compression pipeline of the binary, usable with arbitrary readers and writers.
+//!
+//! The pipeline consists of an input thread that reads records, a number of compute threads that process them,
+//! and an output thread that writes the results.
+//! The threads are connected by bounded channels, such that reading does not run ahead of writing too far.
+//! With more than one compute thread, the results may be written in a different order than the records were read,
+//! unless [`PipelineConfig::ordered`] is set.
+
+use crate::config::CompressionConfig;
+use crate::hodeco::packed::PackedHodecoMapWriter;
+use crate::hodeco::HodecoMap;
+use bio::io::fasta;
+use crossbeam::{channel, thread};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+/// The options of the compression pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineConfig {
+    /// The options of homopolymer compression.
+    pub compression: CompressionConfig<u8>,
+    /// The number of compute threads.
+    /// If zero, the records are processed in the output thread.
+    pub threads: usize,
+    /// The number of records buffered between the input thread and the compute threads.
+    pub input_buffer_size: usize,
+    /// The number of results buffered between the compute threads and the output thread.
+    pub output_buffer_size: usize,
+    /// Drop records with an empty sequence instead of processing them.
+    pub skip_empty: bool,
+    /// The maximum number of bytes of records in flight between the input thread and the output thread.
+    /// If set, the input thread waits before reading on if the records that were read but not yet written exceed this budget, independently of the buffer sizes.
+    ///
+    /// The accounting is approximate: only the id, description and sequence of each record are counted, but not the results of processing it or any allocation overhead.
+    /// A record that exceeds the budget on its own is still processed, but only when no other record is in flight.
+    pub max_memory: Option<usize>,
+    /// Pass the results to the output in the order the records were read, regardless of the number of compute threads.
+    /// Results that are computed early are held back in a reorder buffer until all results before them were output.
+    /// Their records still count against [`max_memory`](Self::max_memory) while they wait.
+    pub ordered: bool,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            compression: Default::default(),
+            threads: 1,
+            input_buffer_size: 32768,
+            output_buffer_size: 32768,
+            skip_empty: false,
+            max_memory: None,
+            ordered: false,
+        }
+    }
+}
+
+/// A budget of bytes of records in flight, used to apply backpressure to the input thread.
+struct MemoryBudget {
+    max: usize,
+    /// The number of bytes in flight, and whether the budget was closed.
+    state: Mutex<(usize, bool)>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            state: Mutex::new((0, false)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait until the given number of bytes fits into the budget and take them.
+    /// Returns false if the budget was closed while waiting.
+    fn acquire(&self, bytes: usize) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        while !state.1 && state.0 > 0 && state.0.saturating_add(bytes) > self.max {
+            state = self
+                .released
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        if state.1 {
+            false
+        } else {
+            state.0 += bytes;
+            true
+        }
+    }
+
+    /// Give back the given number of bytes.
+    fn release(&self, bytes: usize) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).0 -= bytes;
+        self.released.notify_all();
+    }
+
+    /// Wake up and refuse all current and future waiters, since no more bytes will be released.
+    fn close(&self) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).1 = true;
+        self.released.notify_all();
+    }
+}
+
+/// Closes the budget when dropped, such that the input thread does not wait forever if the output thread stops, even by panicking.
+struct CloseOnDrop<'budget>(&'budget MemoryBudget);
+
+impl Drop for CloseOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// The number of bytes of the given record counted against the memory budget.
+fn record_bytes(record: &fasta::Record) -> usize {
+    record.id().len() + record.desc().map(str::len).unwrap_or(0) + record.seq().len()
+}
+
+/// Statistics about a run of the pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of records that were processed.
+    pub records: usize,
+    /// The number of records that were dropped because their sequence was empty.
+    pub skipped_empty_records: usize,
+    /// The total length of the sequences of the processed records.
+    pub original_len: usize,
+}
+
+/// A machine-readable summary of a run of the pipeline, e.g. for orchestration tools.
+///
+/// Unlike [`Stats`], the summary accounts for records that were dropped after they were read, e.g. by filters in the compute threads,
+/// such that only records that were output count as processed, and only their sequences count as input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunReport {
+    /// The number of records that were output.
+    pub records_processed: usize,
+    /// The number of records that were skipped, e.g. because they were empty, invalid or dropped by a filter.
+    pub records_skipped: usize,
+    /// The total length of the original sequences of the processed records.
+    pub input_bytes: usize,
+    /// The total length of the compressed sequences of the processed records.
+    /// Only sequence bytes are counted, but no ids, descriptions or line breaks of the output format.
+    pub output_sequence_bytes: usize,
+}
+
+impl RunReport {
+    /// Create a summary from the statistics of the pipeline, where records dropped because they were empty count as skipped.
+    pub fn from_stats(stats: &Stats) -> Self {
+        Self {
+            records_processed: stats.records,
+            records_skipped: stats.skipped_empty_records,
+            input_bytes: stats.original_len,
+            output_sequence_bytes: 0,
+        }
+    }
+
+    /// Count the given number of processed records, whose original sequences have the given total length, as skipped instead.
+    /// This is for records that were read by the pipeline, but dropped before being output.
+    ///
+    /// Panics if more records or bytes are dropped than were processed.
+    pub fn drop_processed(&mut self, records: usize, input_bytes: usize) {
+        self.records_processed = self
+            .records_processed
+            .checked_sub(records)
+            .expect("dropped more records than were processed");
+        self.input_bytes = self
+            .input_bytes
+            .checked_sub(input_bytes)
+            .expect("dropped more bytes than were processed");
+        self.records_skipped += records;
+    }
+
+    /// Count the given number of records as skipped that never reached the pipeline, e.g. because they could not be parsed.
+    pub fn skip_unread(&mut self, records: usize) {
+        self.records_skipped += records;
+    }
+
+    /// The number of output sequence bytes divided by the number of input bytes, or one if there are no input bytes.
+    pub fn ratio(&self) -> f64 {
+        if self.input_bytes == 0 {
+            1.0
+        } else {
+            self.output_sequence_bytes as f64 / self.input_bytes as f64
+        }
+    }
+
+    /// Write the summary as a single line containing a JSON object, together with the given elapsed time.
+    /// All fields are always written, such that the schema does not depend on the run.
+    pub fn write_json(&self, mut writer: impl Write, elapsed: Duration) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "{{\"records_processed\":{},\"records_skipped\":{},\"input_bytes\":{},\"output_sequence_bytes\":{},\"ratio\":{:.6},\"elapsed_seconds\":{:.3}}}",
+            self.records_processed,
+            self.records_skipped,
+            self.input_bytes,
+            self.output_sequence_bytes,
+            self.ratio(),
+            elapsed.as_secs_f64(),
+        )
+    }
+}
+
+/// An error that stopped the pipeline.
+#[derive(Debug)]
+pub enum PipelineError {
+    /// A record could not be read.
+    Input(std::io::Error),
+    /// A result could not be written.
+    Output(std::io::Error),
+    /// A thread could not be spawned.
+    Thread(std::io::Error),
+    /// The pipeline was cancelled with its cancellation token.
+    Cancelled,
+    /// The pipeline was configured with options that cannot be used together, or with an invalid value.
+    InvalidConfig(String),
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input(error) => write!(f, "cannot read record: {error}"),
+            Self::Output(error) => write!(f, "cannot write record: {error}"),
+            Self::Thread(error) => write!(f, "cannot spawn thread: {error}"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::InvalidConfig(reason) => write!(f, "invalid configuration: {reason}"),
+        }
+    }
+}
+
+impl Error for PipelineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Input(error) | Self::Output(error) | Self::Thread(error) => Some(error),
+            Self::Cancelled | Self::InvalidConfig(_) => None,
+        }
+    }
+}
+
+/// A builder to configure and run the compression pipeline on fasta input.
+///
+/// See the crate documentation for an example.
+pub struct CompressionPipeline<'map> {
+    config: PipelineConfig,
+    hodeco_map: Option<Box<dyn Write + Send + 'map>>,
+    cancellation: Option<Arc<AtomicBool>>,
+}
+
+impl<'map> CompressionPipeline<'map> {
+    /// Create a new builder with the default [`PipelineConfig`].
+    pub fn new() -> Self {
+        Self::with_config(Default::default())
+    }
+
+    /// Create a new builder with the given configuration.
+    pub fn with_config(config: PipelineConfig) -> Self {
+        Self {
+            config,
+            hodeco_map: None,
+            cancellation: None,
+        }
+    }
+
+    /// Set the number of compute threads.
+    /// If zero, the records are processed in the output thread.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = threads;
+        self
+    }
+
+    /// Set the number of records buffered both before and after the compute threads.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.config.input_buffer_size = buffer_size;
+        self.config.output_buffer_size = buffer_size;
+        self
+    }
+
+    /// Set the maximum number of bytes of records in flight, see [`PipelineConfig::max_memory`].
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.config.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Write the records in input order, see [`PipelineConfig::ordered`].
+    pub fn ordered(mut self) -> Self {
+        self.config.ordered = true;
+        self
+    }
+
+    /// Drop records with an empty sequence instead of outputting them as empty records.
+    pub fn skip_empty(mut self) -> Self {
+        self.config.skip_empty = true;
+        self
+    }
+
+    /// Only compress runs of the given symbols, and keep all other runs unchanged, see [`CompressionConfig::only`](crate::config::CompressionConfig::only).
+    pub fn only(mut self, symbols: impl IntoIterator<Item = u8>) -> Self {
+        self.config.compression.only = Some(symbols.into_iter().collect());
+        self
+    }
+
+    /// Keep runs with fewer than `min_run` items unchanged, see [`CompressionConfig::min_run`](crate::config::CompressionConfig::min_run).
+    pub fn min_run(mut self, min_run: usize) -> Self {
+        self.config.compression.min_run = min_run;
+        self
+    }
+
+    /// Shorten compressed runs to at most `max_run` items, see [`CompressionConfig::max_run`](crate::config::CompressionConfig::max_run).
+    /// [`run`](Self::run) rejects a `max_run` of zero.
+    pub fn max_run(mut self, max_run: usize) -> Self {
+        self.config.compression.max_run = max_run;
+        self
+    }
+
+    /// Replace compressed runs by exactly `normalize_length` items, see [`CompressionConfig::normalize_length`](crate::config::CompressionConfig::normalize_length).
+    /// Runs that are lengthened by this cannot be mapped back to the original sequence, so [`run`](Self::run) rejects combining this with [`with_hodeco_map`](Self::with_hodeco_map).
+    pub fn normalize_length(mut self, normalize_length: usize) -> Self {
+        self.config.compression.normalize_length = Some(normalize_length);
+        self
+    }
+
+    /// Also write the hodeco map of each record to the given writer, in the packed format of [`crate::hodeco::packed`].
+    /// The writer is not buffered by the pipeline.
+    pub fn with_hodeco_map(mut self, hodeco_map: impl Write + Send + 'map) -> Self {
+        self.hodeco_map = Some(Box::new(hodeco_map));
+        self
+    }
+
+    /// Stop the pipeline once the given token is set, e.g. from another thread to cancel a long-running compression.
+    ///
+    /// The token is checked between records, and [`run`](Self::run) then returns [`PipelineError::Cancelled`].
+    /// All records that were output before are completely written and flushed, but the remaining records are dropped.
+    pub fn cancellation(mut self, token: Arc<AtomicBool>) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// The configuration of the pipeline.
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// Read all fasta records from `input`, homopolymer compress them, and write them as fasta to `output`.
+    pub fn run<R: Read + Send, W: Write + Send>(
+        self,
+        input: R,
+        output: W,
+    ) -> Result<Stats, PipelineError> {
+        let config = &self.config;
+        // Reject invalid configurations before writing anything, instead of panicking in a compute thread or writing invalid maps.
+        if config.compression.max_run == 0 {
+            return Err(PipelineError::InvalidConfig(
+                "max_run must be positive".to_owned(),
+            ));
+        }
+        if config.compression.normalize_length.is_some() && self.hodeco_map.is_some() {
+            return Err(PipelineError::InvalidConfig(
+                "normalize_length cannot be combined with a hodeco map, since lengthened runs cannot be mapped back to the input".to_owned(),
+            ));
+        }
+        let mut writer = fasta::Writer::new(output);
+        let mut hodeco_map_writer = self
+            .hodeco_map
+            .map(PackedHodecoMapWriter::new)
+            .transpose()
+            .map_err(PipelineError::Output)?;
+        let compute_hodeco_map = hodeco_map_writer.is_some();
+
+        let result = run_pipeline_with_cancellation(
+            fasta::Reader::new(input).records(),
+            config,
+            self.cancellation.as_deref(),
+            |record| {
+                if compute_hodeco_map {
+                    let (sequence, run_lengths): (Vec<u8>, Vec<_>) = config
+                        .compression
+                        .compress_with_run_lengths(record.seq().iter().cloned())
+                        .unzip();
+                    let hodeco_map =
+                        HodecoMap::from_run_lengths(record.id().to_owned(), run_lengths, None);
+                    (record, sequence, Some(hodeco_map))
+                } else {
+                    let sequence = config.compression.compress(record.seq().iter().cloned());
+                    (record, sequence, None)
+                }
+            },
+            &mut |(record, sequence, hodeco_map): (fasta::Record, Vec<u8>, Option<HodecoMap>)| {
+                writer.write(record.id(), record.desc(), &sequence)?;
+                if let (Some(hodeco_map_writer), Some(hodeco_map)) =
+                    (hodeco_map_writer.as_mut(), hodeco_map)
+                {
+                    hodeco_map_writer.write(&hodeco_map)?;
+                }
+                Ok(())
+            },
+        );
+        if let Err(PipelineError::Input(_) | PipelineError::Thread(_)) = result {
+            return result;
+        }
+        // When cancelled, the records that were written before are flushed as well, and the hodeco map is finished with an index of these records.
+        writer.flush().map_err(PipelineError::Output)?;
+        if let Some(hodeco_map_writer) = hodeco_map_writer {
+            hodeco_map_writer.finish().map_err(PipelineError::Output)?;
+        }
+        result
+    }
+}
+
+impl Default for CompressionPipeline<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read all fasta records from `input`, homopolymer compress them according to `config`, and write them as fasta to `output`.
+pub fn run_compression<R: Read + Send, W: Write + Send>(
+    input: R,
+    output: W,
+    config: &PipelineConfig,
+) -> Result<Stats, PipelineError> {
+    CompressionPipeline::with_config(config.clone()).run(input, output)
+}
+
+/// Run the pipeline on the given records, processing each record with `compute` in the compute threads, and passing each result to `sink` in the output thread.
+///
+/// The pipeline stops at the first error, and returns it after all threads have finished.
+/// If a thread panics, the panic is propagated to the caller.
+pub fn run_pipeline<Output: Send>(
+    records: impl Iterator<Item = std::io::Result<fasta::Record>> + Send,
+    config: &PipelineConfig,
+    compute: impl Fn(fasta::Record) -> Output + Sync,
+    sink: &mut (impl FnMut(Output) -> std::io::Result<()> + Send),
+) -> Result<Stats, PipelineError> {
+    run_pipeline_with_cancellation(records, config, None, compute, sink)
+}
+
+/// Run the pipeline as [`run_pipeline`], but stop with [`PipelineError::Cancelled`] once the `cancellation` token is set.
+///
+/// Each thread checks the token between records.
+/// The output thread does not pass any more results to `sink` once it observed the token.
+fn run_pipeline_with_cancellation<Output: Send>(
+    records: impl Iterator<Item = std::io::Result<fasta::Record>> + Send,
+    config: &PipelineConfig,
+    cancellation: Option<&AtomicBool>,
+    compute: impl Fn(fasta::Record) -> Output + Sync,
+    sink: &mut (impl FnMut(Output) -> std::io::Result<()> + Send),
+) -> Result<Stats, PipelineError> {
+    let cancelled = move || cancellation.map_or(false, |token| token.load(Ordering::Relaxed));
+    let compute = &compute;
+    let budget = &MemoryBudget::new(config.max_memory.unwrap_or(usize::MAX));
+    thread::scope(|scope| {
+        let (input_sender, input_receiver) = channel::bounded(config.input_buffer_size);
+        let skip_empty = config.skip_empty;
+        let ordered = config.ordered;
+        let input_thread = scope
+            .builder()
+            .name("input_thread".to_string())
+            .spawn(move |_| {
+                let mut stats = Stats::default();
+                for record in records {
+                    if cancelled() {
+                        // The output thread reports the cancellation.
+                        break;
+                    }
+                    let record = record.map_err(PipelineError::Input)?;
+                    if skip_empty && record.seq().is_empty() {
+                        stats.skipped_empty_records += 1;
+                        continue;
+                    }
+                    stats.records += 1;
+                    stats.original_len += record.seq().len();
+                    let bytes = record_bytes(&record);
+                    let index = stats.records - 1;
+                    if !budget.acquire(bytes) || input_sender.send((index, record, bytes)).is_err()
+                    {
+                        // The output thread stopped early and reports the reason.
+                        break;
+                    }
+                }
+                Ok(stats)
+            })
+            .map_err(PipelineError::Thread)?;
+
+        let output_thread = if config.threads == 0 {
+            scope
+                .builder()
+                .name("output_thread".to_string())
+                .spawn(move |_| {
+                    let _close = CloseOnDrop(budget);
+                    while let Ok((_, record, bytes)) = input_receiver.recv() {
+                        if cancelled() {
+                            return Err(PipelineError::Cancelled);
+                        }
+                        sink(compute(record)).map_err(PipelineError::Output)?;
+                        budget.release(bytes);
+                    }
+                    if cancelled() {
+                        return Err(PipelineError::Cancelled);
+                    }
+                    Ok(())
+                })
+                .map_err(PipelineError::Thread)?
+        } else {
+            let (output_sender, output_receiver) = channel::bounded(config.output_buffer_size);
+            for thread_id in 0..config.threads {
+                let input_receiver = input_receiver.clone();
+                let output_sender = output_sender.clone();
+                scope
+                    .builder()
+                    .name(format!("compute_thread_{thread_id}"))
+                    .spawn(move |_| {
+                        while let Ok((index, record, bytes)) = input_receiver.recv() {
+                            if cancelled() {
+                                break;
+                            }
+                            if output_sender.send((index, compute(record), bytes)).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .map_err(PipelineError::Thread)?;
+            }
+            // Otherwise, the input thread would block forever if the compute threads stop early.
+            drop(input_receiver);
+            scope
+                .builder()
+                .name("output_thread".to_string())
+                .spawn(move |_| {
+                    let _close = CloseOnDrop(budget);
+                    // Results that arrived before some result preceding them in input order.
+                    let mut reorder_buffer = BTreeMap::new();
+                    let mut next_index = 0;
+                    while let Ok((index, output, bytes)) = output_receiver.recv() {
+                        if cancelled() {
+                            return Err(PipelineError::Cancelled);
+                        }
+                        if !ordered {
+                            sink(output).map_err(PipelineError::Output)?;
+                            budget.release(bytes);
+                            continue;
+                        }
+                        reorder_buffer.insert(index, (output, bytes));
+                        while let Some((output, bytes)) = reorder_buffer.remove(&next_index) {
+                            sink(output).map_err(PipelineError::Output)?;
+                            budget.release(bytes);
+                            next_index += 1;
+                        }
+                    }
+                    if cancelled() {
+                        return Err(PipelineError::Cancelled);
+                    }
+                    Ok(())
+                })
+                .map_err(PipelineError::Thread)?
+        };
+
+        let output_result = output_thread
+            .join()
+            .unwrap_or_else(|error| panic::resume_unwind(error));
+        let stats = input_thread
+            .join()
+            .unwrap_or_else(|error| panic::resume_unwind(error))?;
+        output_result.map(|()| stats)
+    })
+    .unwrap_or_else(|error| panic::resume_unwind(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hodeco::packed::PackedHodecoMapReader;
+    use crate::pipeline::{
+        run_compression, CompressionPipeline, MemoryBudget, PipelineConfig, PipelineError,
+        RunReport, Stats,
+    };
+    use std::io::{ErrorKind, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_compression() {
+        let input = b">r1 first\nAAACCG\nGT\n>r2\n\n>r3\nTTTTA\n";
+        for threads in [0, 1] {
+            let mut output = Vec::new();
+            let config = PipelineConfig {
+                threads,
+                ..Default::default()
+            };
+            let stats = run_compression(&input[..], &mut output, &config).unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                ">r1 first\nACGT\n>r2\n\n>r3\nTA\n"
+            );
+            assert_eq!(
+                stats,
+                Stats {
+                    records: 3,
+                    skipped_empty_records: 0,
+                    original_len: 13,
+                }
+            );
+        }
+
+        let mut output = Vec::new();
+        let config = PipelineConfig {
+            skip_empty: true,
+            compression: crate::config::CompressionConfig {
+                only: Some([b'A'].into_iter().collect()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let stats = run_compression(&input[..], &mut output, &config).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">r1 first\nACCGGT\n>r3\nTTTTA\n"
+        );
+        assert_eq!(stats.skipped_empty_records, 1);
+    }
+
+    #[test]
+    fn test_builder() {
+        let input = b">r1\nAAACCGT\n>r2\n\n>r3\nTTAA\n";
+        let mut output = Vec::new();
+        let mut hodeco_map = Vec::new();
+        let pipeline = CompressionPipeline::new()
+            .threads(2)
+            .buffer_size(4)
+            .max_memory(1000)
+            .skip_empty()
+            .only(*b"AC")
+            .with_hodeco_map(&mut hodeco_map);
+        assert_eq!(pipeline.config().input_buffer_size, 4);
+        let stats = pipeline.run(&input[..], &mut output).unwrap();
+        assert_eq!(stats.records, 2);
+        assert_eq!(stats.skipped_empty_records, 1);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(">r1\nACGT\n"));
+        assert!(output.contains(">r3\nTTA\n"));
+        let mut maps: Vec<_> = PackedHodecoMapReader::new(&hodeco_map[..])
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        maps.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(maps.len(), 2);
+        assert_eq!(maps[0].decompress(b"ACGT").unwrap(), b"AAACCGT");
+        assert_eq!(maps[1].decompress(b"TTA").unwrap(), b"TTAA");
+    }
+
+    #[test]
+    fn test_builder_run_lengths() {
+        let input = b">r1\nAAAACCGGGT\n";
+        let mut output = Vec::new();
+        CompressionPipeline::new()
+            .min_run(3)
+            .max_run(2)
+            .run(&input[..], &mut output)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), ">r1\nAACCGGT\n");
+
+        let mut output = Vec::new();
+        CompressionPipeline::new()
+            .normalize_length(2)
+            .run(&input[..], &mut output)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), ">r1\nAACCGGTT\n");
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let input = b">r1\nAAAACCGGGT\n";
+        let mut output = Vec::new();
+        let result = CompressionPipeline::new()
+            .max_run(0)
+            .run(&input[..], &mut output);
+        assert!(matches!(result, Err(PipelineError::InvalidConfig(_))));
+
+        let mut hodeco_map = Vec::new();
+        let result = CompressionPipeline::new()
+            .normalize_length(2)
+            .with_hodeco_map(&mut hodeco_map)
+            .run(&input[..], &mut output);
+        assert!(matches!(result, Err(PipelineError::InvalidConfig(_))));
+        assert!(output.is_empty());
+        assert!(hodeco_map.is_empty());
+    }
+
+    #[test]
+    fn test_ordered() {
+        let input: Vec<u8> = (0..1000)
+            .flat_map(|index| format!(">r{index}\n{}\n", "AAC".repeat(index % 50)).into_bytes())
+            .collect();
+        let mut expected_output = None;
+        let mut expected_hodeco_map = None;
+        for threads in [0, 1, 2, 4, 8] {
+            for max_memory in [None, Some(100)] {
+                let mut output = Vec::new();
+                let mut hodeco_map = Vec::new();
+                let mut pipeline = CompressionPipeline::new()
+                    .threads(threads)
+                    .buffer_size(4)
+                    .ordered()
+                    .with_hodeco_map(&mut hodeco_map);
+                if let Some(max_memory) = max_memory {
+                    pipeline = pipeline.max_memory(max_memory);
+                }
+                pipeline.run(&input[..], &mut output).unwrap();
+                assert_eq!(
+                    output,
+                    *expected_output.get_or_insert_with(|| output.clone())
+                );
+                assert_eq!(
+                    hodeco_map,
+                    *expected_hodeco_map.get_or_insert_with(|| hodeco_map.clone())
+                );
+            }
+        }
+        let expected_output = String::from_utf8(expected_output.unwrap()).unwrap();
+        assert!(expected_output.starts_with(">r0\n\n>r1\nAC\n>r2\nACAC\n"));
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let input = b">r1\nAAACCCCCCCCCCCCGT\n>r2\nTTTTA\n".repeat(100);
+        let expected = b">r1\nACGT\n>r2\nTA\n".repeat(100);
+        for threads in [0, 1, 4] {
+            for max_memory in [1, 30, 1000] {
+                let mut output = Vec::new();
+                let config = PipelineConfig {
+                    threads,
+                    max_memory: Some(max_memory),
+                    ..Default::default()
+                };
+                run_compression(&input[..], &mut output, &config).unwrap();
+                if threads <= 1 {
+                    assert_eq!(output, expected);
+                } else {
+                    assert_eq!(output.len(), expected.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(15);
+        assert!(budget.acquire(10));
+        let acquired = AtomicBool::new(false);
+        crossbeam::thread::scope(|scope| {
+            let waiter = scope.spawn(|_| {
+                // Does not fit next to the 10 bytes in flight, so this blocks until they are released.
+                let result = budget.acquire(10);
+                acquired.store(true, Ordering::SeqCst);
+                result
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!acquired.load(Ordering::SeqCst));
+            budget.release(10);
+            assert!(waiter.join().unwrap());
+        })
+        .unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+        assert_eq!(budget.state.lock().unwrap().0, 10);
+
+        // A record larger than the whole budget is admitted once nothing else is in flight.
+        budget.release(10);
+        assert!(budget.acquire(100));
+
+        // Closing the budget wakes up waiting threads and refuses them.
+        crossbeam::thread::scope(|scope| {
+            let waiter = scope.spawn(|_| budget.acquire(1));
+            std::thread::sleep(Duration::from_millis(100));
+            budget.close();
+            assert!(!waiter.join().unwrap());
+        })
+        .unwrap();
+        assert!(!budget.acquire(1));
+    }
+
+    /// A writer that sets a cancellation token once it received the given number of bytes.
+    struct CancellingWriter {
+        output: Vec<u8>,
+        cancel_after: usize,
+        token: Arc<AtomicBool>,
+    }
+
+    impl Write for CancellingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            if self.output.len() >= self.cancel_after {
+                self.token.store(true, Ordering::Relaxed);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let input = b">r1\nAAACCCCCCCCCCCCGT\n>r2\nTTTTA\n".repeat(100_000);
+        let expected = b">r1\nACGT\n>r2\nTA\n".repeat(100_000);
+        for threads in [0, 1, 4] {
+            let token = Arc::new(AtomicBool::new(false));
+            let mut writer = CancellingWriter {
+                output: Vec::new(),
+                cancel_after: 10_000,
+                token: token.clone(),
+            };
+            let result = CompressionPipeline::new()
+                .threads(threads)
+                .buffer_size(16)
+                .cancellation(token)
+                .run(&input[..], &mut writer);
+            assert!(matches!(result, Err(PipelineError::Cancelled)));
+
+            // The output stops promptly, and only after complete records.
+            let output = writer.output;
+            assert!(output.len() >= 10_000);
+            assert!(output.len() < expected.len() / 10);
+            assert!(output.ends_with(b"\n"));
+            if threads <= 1 {
+                assert_eq!(output, expected[..output.len()]);
+            }
+        }
+
+        // A token that is set before running stops the pipeline before any record is written.
+        let mut output = Vec::new();
+        let result = CompressionPipeline::new()
+            .cancellation(Arc::new(AtomicBool::new(true)))
+            .run(&input[..], &mut output);
+        assert!(matches!(result, Err(PipelineError::Cancelled)));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_run_report() {
+        let input = b">r1\nAAACCG\n>r2\n\n>r3\nTTTTAAAA\n>r4\nACGT\n";
+        let config = PipelineConfig {
+            skip_empty: true,
+            ..Default::default()
+        };
+        let stats = run_compression(&input[..], Vec::new(), &config).unwrap();
+        let mut report = RunReport::from_stats(&stats);
+        assert_eq!(
+            report,
+            RunReport {
+                records_processed: 3,
+                records_skipped: 1,
+                input_bytes: 18,
+                output_sequence_bytes: 0,
+            }
+        );
+
+        // A filter drops r3 after it was read, so neither it nor its bytes are processed.
+        report.drop_processed(1, 8);
+        report.skip_unread(2);
+        report.output_sequence_bytes = 7;
+        assert_eq!(report.records_processed, 2);
+        assert_eq!(report.records_skipped, 4);
+        assert_eq!(report.input_bytes, 10);
+        assert_eq!(report.ratio(), 0.7);
+
+        let mut json = Vec::new();
+        report
+            .write_json(&mut json, Duration::from_millis(1500))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"records_processed\":2,\"records_skipped\":4,\"input_bytes\":10,\"output_sequence_bytes\":7,\"ratio\":0.700000,\"elapsed_seconds\":1.500}\n"
+        );
+        assert_eq!(RunReport::default().ratio(), 1.0);
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let result = run_compression(&b"ACGT\n"[..], Vec::new(), &Default::default());
+        assert!(matches!(result, Err(PipelineError::Input(_))));
+
+        let input = b">r1\nAAAC\n".repeat(10_000);
+        let result = run_compression(&input[..], FailingWriter, &Default::default());
+        assert!(matches!(result, Err(PipelineError::Output(_))));
+
+        let config = PipelineConfig {
+            max_memory: Some(10),
+            ..Default::default()
+        };
+        let result = run_compression(&input[..], FailingWriter, &config);
+        assert!(matches!(result, Err(PipelineError::Output(_))));
+    }
+}
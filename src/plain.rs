@@ -0,0 +1,198 @@
+//! Reading and writing plain text with one sequence per line.
+
+use bio::io::fasta;
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+
+/// An iterator over the lines of a plain text input, without their line endings.
+///
+/// Lines may be terminated by `\n`, `\r\n` or a bare `\r`, such that carriage returns never become part of a sequence.
+/// A final line without terminator is returned as well.
+pub struct PlainLines<R> {
+    reader: R,
+}
+
+impl<R: BufRead> PlainLines<R> {
+    /// Create a new iterator over the lines of the given reader.
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Consume a `\n` directly following a `\r`.
+    fn skip_line_feed(&mut self) -> Result<()> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buffer) => {
+                    if buffer.first() == Some(&b'\n') {
+                        self.reader.consume(1);
+                    }
+                    return Ok(());
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PlainLines<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        let mut is_eof = true;
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Some(Err(error)),
+            };
+            if buffer.is_empty() {
+                return if is_eof { None } else { Some(Ok(line)) };
+            }
+            is_eof = false;
+
+            if let Some(position) = buffer
+                .iter()
+                .position(|&byte| byte == b'\n' || byte == b'\r')
+            {
+                let is_carriage_return = buffer[position] == b'\r';
+                line.extend_from_slice(&buffer[..position]);
+                self.reader.consume(position + 1);
+                if is_carriage_return {
+                    if let Err(error) = self.skip_line_feed() {
+                        return Some(Err(error));
+                    }
+                }
+                return Some(Ok(line));
+            } else {
+                let len = buffer.len();
+                line.extend_from_slice(buffer);
+                self.reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Read the lines of a plain text input as fasta records, where the id of each record is its line number, starting from one.
+///
+/// Since the sequences of fasta records are text, a line that is not valid UTF-8 is returned as an error of kind [`ErrorKind::InvalidData`].
+/// The iterator continues with the next line after such an error.
+pub fn plain_records<R: BufRead>(reader: R) -> impl Iterator<Item = Result<fasta::Record>> {
+    PlainLines::new(reader).enumerate().map(|(index, line)| {
+        let line = line?;
+        let id = (index + 1).to_string();
+        std::str::from_utf8(&line).map_err(|error| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("line {id} is not valid UTF-8: {error}"),
+            )
+        })?;
+        Ok(fasta::Record::with_attrs(&id, None, &line))
+    })
+}
+
+/// A writer of plain text output with one sequence per line, terminated by `\n`.
+///
+/// This is the counterpart of [`PlainLines`], and writes no ids or descriptions.
+pub struct PlainWriter<W: Write> {
+    writer: W,
+    line: Vec<u8>,
+}
+
+impl<W: Write> PlainWriter<W> {
+    /// Create a new plain writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            line: Vec::new(),
+        }
+    }
+
+    /// Write the given sequence as a single line.
+    pub fn write_sequence(&mut self, sequence: impl IntoIterator<Item = u8>) -> Result<()> {
+        self.line.extend(sequence);
+        self.line.push(b'\n');
+        let result = self.writer.write_all(&self.line);
+        self.line.clear();
+        result
+    }
+
+    /// Flush the writer, ensuring that everything is written.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::homopolymer_compress;
+    use crate::plain::{plain_records, PlainLines, PlainWriter};
+    use std::io::{BufReader, ErrorKind};
+
+    fn lines(input: &[u8]) -> Vec<Vec<u8>> {
+        PlainLines::new(input).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(lines(b"AC\nGT\n"), [b"AC", b"GT"]);
+        assert_eq!(lines(b"AC\r\nGT\r\n"), [b"AC", b"GT"]);
+        assert_eq!(lines(b"AC\rGT\r"), [b"AC", b"GT"]);
+        assert_eq!(lines(b"AC\r\nGT"), [b"AC", b"GT"]);
+        assert_eq!(lines(b"AC\r\n\r\nGT"), [&b"AC"[..], b"", b"GT"]);
+        assert!(lines(b"").is_empty());
+    }
+
+    #[test]
+    fn test_line_ending_across_buffer_boundary() {
+        // A buffer of one byte splits every `\r\n` between two reads.
+        let reader = BufReader::with_capacity(1, &b"AAC\r\nGT\r\nT"[..]);
+        let lines: Vec<_> = PlainLines::new(reader).map(Result::unwrap).collect();
+        assert_eq!(lines, [&b"AAC"[..], b"GT", b"T"]);
+    }
+
+    #[test]
+    fn test_plain_records() {
+        let records: Vec<_> = plain_records(&b"AAC\nGT\n"[..])
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id(), "2");
+        assert_eq!(records[1].seq(), b"GT");
+    }
+
+    #[test]
+    fn test_plain_records_non_utf8() {
+        let mut records = plain_records(&b"AAC\xffGG\nTTA\n"[..]);
+        let error = records.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 1"));
+        // The following line is still read.
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.id(), "2");
+        assert_eq!(record.seq(), b"TTA");
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let mut output = Vec::new();
+        let mut writer = PlainWriter::new(&mut output);
+        for sequence in [&b"AAC"[..], b"", b"GT"] {
+            writer
+                .write_sequence(homopolymer_compress(sequence.iter().copied()))
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(output, b"AC\n\nGT\n");
+        assert_eq!(lines(&output), [&b"AC"[..], b"", b"GT"]);
+    }
+
+    #[test]
+    fn test_crlf_never_reaches_compressor() {
+        for line in lines(b"AAAC\r\nCCGT\r\nTTTTT\r\n") {
+            let compressed: Vec<_> = homopolymer_compress(line).collect();
+            assert!(!compressed.contains(&b'\r'));
+        }
+    }
+}
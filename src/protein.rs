@@ -0,0 +1,69 @@
+//! Homopolymer compression of protein sequences with equivalence classes of amino acids.
+
+/// Homopolymer compress the given sequence, treating adjacent bytes as part of the same run if they belong to the same class.
+///
+/// Each byte is mapped to its class by `class_of`, and each run of bytes with the same class is replaced by its first byte.
+/// This is lossy not only with respect to the length of the runs, but also with respect to the exact residues within a run, since all but the first are dropped.
+pub fn homopolymer_compress_classes<'input>(
+    input: &'input [u8],
+    class_of: impl 'input + Fn(u8) -> u8,
+) -> impl 'input + Iterator<Item = u8> {
+    let mut previous_class = None;
+    input.iter().copied().filter(move |&residue| {
+        let class = Some(class_of(residue));
+        if class == previous_class {
+            false
+        } else {
+            previous_class = class;
+            true
+        }
+    })
+}
+
+/// Map an amino acid given as one-letter code to a class of amino acids with similar hydrophobicity and charge.
+///
+/// The classes are:
+///  * hydrophobic aliphatic: `A`, `C`, `I`, `L`, `M`, `V`,
+///  * aromatic: `F`, `W`, `Y`,
+///  * polar: `N`, `Q`, `S`, `T`,
+///  * positively charged: `H`, `K`, `R`,
+///  * negatively charged: `D`, `E`,
+///  * `G` and `P`, each in a class of its own.
+///
+/// Each class is identified by one of its members, and any other byte is a class of its own.
+/// Lower case letters belong to the same class as their upper case versions.
+pub fn hydrophobicity_class(residue: u8) -> u8 {
+    match residue.to_ascii_uppercase() {
+        b'A' | b'C' | b'I' | b'L' | b'M' | b'V' => b'A',
+        b'F' | b'W' | b'Y' => b'F',
+        b'N' | b'Q' | b'S' | b'T' => b'N',
+        b'H' | b'K' | b'R' => b'H',
+        b'D' | b'E' => b'D',
+        residue => residue,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protein::{homopolymer_compress_classes, hydrophobicity_class};
+
+    #[test]
+    fn test_custom_classes() {
+        // Only I, L and V are equivalent.
+        let class_of = |residue| match residue {
+            b'I' | b'L' | b'V' => b'I',
+            residue => residue,
+        };
+        let compressed: Vec<_> = homopolymer_compress_classes(b"MILVVKKLAIG", class_of).collect();
+        assert_eq!(compressed, b"MIKLAIG");
+    }
+
+    #[test]
+    fn test_hydrophobicity_classes() {
+        let compressed: Vec<_> =
+            homopolymer_compress_classes(b"MAILKRHDEGGPPFWYSTX", hydrophobicity_class).collect();
+        assert_eq!(compressed, b"MKDGPFSX");
+        assert_eq!(hydrophobicity_class(b'l'), hydrophobicity_class(b'V'));
+        assert_ne!(hydrophobicity_class(b'X'), hydrophobicity_class(b'A'));
+    }
+}
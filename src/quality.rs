@@ -0,0 +1,385 @@
+//! Merging the quality scores of a homopolymer run into a single quality score.
+
+use crate::homopolymer_compress_with_parallel;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The offset of Phred quality scores encoded as ASCII bytes.
+pub const PHRED_OFFSET: u8 = 33;
+
+/// The highest Phred quality score that can be encoded as a printable ASCII byte, i.e. as `~`.
+pub const MAX_PHRED_SCORE: u8 = b'~' - PHRED_OFFSET;
+
+/// An ASCII-encoded quality score is below [`PHRED_OFFSET`], so it does not encode a Phred score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidQuality {
+    /// The index of the quality score in the given qualities.
+    pub position: usize,
+    /// The invalid quality score.
+    pub quality: u8,
+}
+
+impl Display for InvalidQuality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid Phred quality score {} at position {}",
+            self.quality, self.position
+        )
+    }
+}
+
+impl Error for InvalidQuality {}
+
+/// Check that all quality scores encode Phred scores, and return the first one that does not as an error.
+fn validate_qualities(qualities: &[u8]) -> Result<(), InvalidQuality> {
+    match qualities.iter().position(|&quality| quality < PHRED_OFFSET) {
+        Some(position) => Err(InvalidQuality {
+            position,
+            quality: qualities[position],
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A strategy to aggregate the quality scores of a homopolymer run into the quality score of its compressed item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityMerge {
+    /// Keep the quality of the first item of the run.
+    First,
+    /// Keep the quality of the last item of the run.
+    Last,
+    /// Keep the minimum quality of the run.
+    Min,
+    /// Keep the maximum quality of the run.
+    Max,
+    /// Keep the arithmetic mean of the Phred scores of the run, rounded to the nearest integer.
+    Mean,
+    /// Keep the median quality of the run.
+    /// For runs of even length, the lower of the two middle qualities is kept.
+    Median,
+}
+
+impl QualityMerge {
+    /// Aggregate the ASCII-encoded quality scores of a run into a single ASCII-encoded quality score.
+    ///
+    /// The quality scores are expected to be Phred scores offset by [`PHRED_OFFSET`], and an error is returned for the first one that is not.
+    ///
+    /// Panics if `qualities` is empty.
+    pub fn aggregate(&self, qualities: &[u8]) -> Result<u8, InvalidQuality> {
+        assert!(!qualities.is_empty(), "Cannot aggregate an empty run");
+        validate_qualities(qualities)?;
+        let mut scores = qualities.iter().map(|quality| quality - PHRED_OFFSET);
+
+        let score = match self {
+            Self::First => scores.next().unwrap(),
+            Self::Last => scores.next_back().unwrap(),
+            Self::Min => scores.min().unwrap(),
+            Self::Max => scores.max().unwrap(),
+            Self::Mean => {
+                let sum: usize = scores.map(usize::from).sum();
+                ((sum + qualities.len() / 2) / qualities.len()) as u8
+            }
+            Self::Median => {
+                let mut scores: Vec<_> = scores.collect();
+                scores.sort_unstable();
+                scores[(scores.len() - 1) / 2]
+            }
+        };
+        Ok(score + PHRED_OFFSET)
+    }
+}
+
+/// The name of a [`QualityMerge`] variant could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownQualityMerge(pub String);
+
+impl FromStr for QualityMerge {
+    type Err = UnknownQualityMerge;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            other => Err(UnknownQualityMerge(other.to_string())),
+        }
+    }
+}
+
+impl Display for QualityMerge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::First => "first",
+                Self::Last => "last",
+                Self::Min => "min",
+                Self::Max => "max",
+                Self::Mean => "mean",
+                Self::Median => "median",
+            }
+        )
+    }
+}
+
+impl Display for UnknownQualityMerge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown quality merge {:?}, expected one of first, last, min, max, mean, median",
+            self.0
+        )
+    }
+}
+
+impl Error for UnknownQualityMerge {}
+
+/// A table of quality bins that maps each quality score to the representative of its bin, e.g. to reduce the entropy of the qualities.
+///
+/// Each bin is given by its lowest Phred score and its representative Phred score, and contains all scores up to the lowest score of the next bin.
+/// Scores below the lowest score of the first bin are not binned and stay unchanged.
+/// As text, the bins are written as comma-separated `lowest:representative` pairs, e.g. `2:6,10:15,20:22`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityBins {
+    bins: Vec<(u8, u8)>,
+}
+
+impl QualityBins {
+    /// Create a table from the given bins, given as their lowest and representative Phred scores.
+    /// The lowest scores must be strictly increasing, the representatives must be at most [`MAX_PHRED_SCORE`], and there must be at least one bin.
+    pub fn new(bins: impl IntoIterator<Item = (u8, u8)>) -> Result<Self, InvalidQualityBins> {
+        let bins: Vec<_> = bins.into_iter().collect();
+        if bins.is_empty() {
+            return Err(InvalidQualityBins(
+                "there must be at least one bin".to_owned(),
+            ));
+        }
+        if bins.windows(2).any(|window| window[0].0 >= window[1].0) {
+            return Err(InvalidQualityBins(
+                "the lowest scores of the bins must be strictly increasing".to_owned(),
+            ));
+        }
+        if let Some(&(_, representative)) = bins
+            .iter()
+            .find(|&&(_, representative)| representative > MAX_PHRED_SCORE)
+        {
+            return Err(InvalidQualityBins(format!(
+                "the representative {representative} is above the maximum Phred score {MAX_PHRED_SCORE}"
+            )));
+        }
+        Ok(Self { bins })
+    }
+
+    /// The 8-level binning used by Illumina sequencers, which keeps the scores 0 and 1 and bins all higher scores.
+    pub fn illumina() -> Self {
+        Self {
+            bins: vec![
+                (2, 6),
+                (10, 15),
+                (20, 22),
+                (25, 27),
+                (30, 33),
+                (35, 37),
+                (40, 40),
+            ],
+        }
+    }
+
+    /// Map the given ASCII-encoded quality score to the ASCII-encoded representative of its bin.
+    ///
+    /// The quality score is expected to be a Phred score offset by [`PHRED_OFFSET`], and an error is returned otherwise.
+    pub fn bin(&self, quality: u8) -> Result<u8, InvalidQuality> {
+        validate_qualities(&[quality])?;
+        let score = quality - PHRED_OFFSET;
+        let bin = self.bins.partition_point(|&(lowest, _)| lowest <= score);
+        Ok(if bin == 0 {
+            quality
+        } else {
+            self.bins[bin - 1].1 + PHRED_OFFSET
+        })
+    }
+}
+
+/// A [`QualityBins`] table could not be created or parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidQualityBins(pub String);
+
+impl FromStr for QualityBins {
+    type Err = InvalidQualityBins;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let bins = string
+            .split(',')
+            .map(|bin| {
+                bin.split_once(':')
+                    .and_then(|(lowest, representative)| {
+                        Some((lowest.parse().ok()?, representative.parse().ok()?))
+                    })
+                    .ok_or_else(|| {
+                        InvalidQualityBins(format!(
+                            "expected a bin as lowest:representative Phred score, but got {bin:?}"
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(bins)
+    }
+}
+
+impl Display for InvalidQualityBins {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid quality bins: {}", self.0)
+    }
+}
+
+impl Error for InvalidQualityBins {}
+
+/// Homopolymer compress the given fastq sequence and merge the qualities of each run with `merge`.
+///
+/// If `bins` are given, then each merged quality is additionally mapped to the representative of its bin.
+/// Returns the compressed sequence and its qualities, which have equal length.
+/// Since fastq readers usually do not validate the qualities, all qualities are checked first, and an error is returned for the first one that is not a Phred score offset by [`PHRED_OFFSET`].
+///
+/// Panics if `sequence` and `qualities` have different lengths.
+pub fn homopolymer_compress_fastq(
+    sequence: &[u8],
+    qualities: &[u8],
+    merge: QualityMerge,
+    bins: Option<&QualityBins>,
+) -> Result<(Vec<u8>, Vec<u8>), InvalidQuality> {
+    validate_qualities(qualities)?;
+    Ok(homopolymer_compress_with_parallel(
+        sequence,
+        qualities,
+        |run| {
+            let quality = merge
+                .aggregate(run)
+                .unwrap_or_else(|_| unreachable!("the qualities were validated"));
+            bins.map(|bins| {
+                bins.bin(quality)
+                    .unwrap_or_else(|_| unreachable!("merged qualities are valid"))
+            })
+            .unwrap_or(quality)
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quality::{
+        homopolymer_compress_fastq, InvalidQuality, QualityBins, QualityMerge, PHRED_OFFSET,
+    };
+
+    /// Phred scores 30, 10, 40, 20, encoded as ASCII.
+    const RUN: &[u8] = b"?+I5";
+
+    #[test]
+    fn test_aggregation() {
+        let aggregate = |merge: QualityMerge| merge.aggregate(RUN).unwrap() - PHRED_OFFSET;
+        assert_eq!(aggregate(QualityMerge::First), 30);
+        assert_eq!(aggregate(QualityMerge::Last), 20);
+        assert_eq!(aggregate(QualityMerge::Min), 10);
+        assert_eq!(aggregate(QualityMerge::Max), 40);
+        assert_eq!(aggregate(QualityMerge::Mean), 25);
+        assert_eq!(aggregate(QualityMerge::Median), 20);
+    }
+
+    #[test]
+    fn test_single_quality() {
+        for merge in ["first", "last", "min", "max", "mean", "median"] {
+            let merge: QualityMerge = merge.parse().unwrap();
+            assert_eq!(merge.aggregate(b"!"), Ok(b'!'));
+            assert_eq!(merge.aggregate(b"~"), Ok(b'~'));
+        }
+    }
+
+    #[test]
+    fn test_mean_rounding() {
+        // Phred scores 0 and 1 average to 0.5, which rounds up.
+        assert_eq!(QualityMerge::Mean.aggregate(b"!\""), Ok(b'"'));
+        // Phred scores 0, 0 and 1 average to 0.33, which rounds down.
+        assert_eq!(QualityMerge::Mean.aggregate(b"!!\""), Ok(b'!'));
+    }
+
+    #[test]
+    fn test_invalid_quality() {
+        let error = InvalidQuality {
+            position: 1,
+            quality: b' ',
+        };
+        for merge in ["first", "last", "min", "max", "mean", "median"] {
+            let merge: QualityMerge = merge.parse().unwrap();
+            assert_eq!(merge.aggregate(b"I I"), Err(error));
+        }
+        assert_eq!(
+            QualityBins::illumina().bin(0),
+            Err(InvalidQuality {
+                position: 0,
+                quality: 0
+            })
+        );
+        assert_eq!(
+            homopolymer_compress_fastq(b"AAC", b"II\n", QualityMerge::Max, None),
+            Err(InvalidQuality {
+                position: 2,
+                quality: b'\n'
+            })
+        );
+    }
+
+    #[test]
+    fn test_quality_bins() {
+        let bins: QualityBins = "2:6,10:15,20:22,25:27,30:33,35:37,40:40".parse().unwrap();
+        assert_eq!(bins, QualityBins::illumina());
+        let bin = |score: u8| bins.bin(score + PHRED_OFFSET).unwrap() - PHRED_OFFSET;
+        assert_eq!(bin(0), 0);
+        assert_eq!(bin(2), 6);
+        assert_eq!(bin(9), 6);
+        assert_eq!(bin(10), 15);
+        assert_eq!(bin(24), 22);
+        assert_eq!(bin(41), 40);
+
+        assert!("".parse::<QualityBins>().is_err());
+        assert!("10:15,2:6".parse::<QualityBins>().is_err());
+        assert!("10-15".parse::<QualityBins>().is_err());
+        assert!("0:250".parse::<QualityBins>().is_err());
+        assert!("0:94".parse::<QualityBins>().is_err());
+        let bins: QualityBins = "0:93".parse().unwrap();
+        assert_eq!(bins.bin(b'!'), Ok(b'~'));
+    }
+
+    #[test]
+    fn test_compress_fastq() {
+        let sequence = b"AAACGGTT";
+        // Phred scores 30, 10, 40, 20, 20, 38, 2, 31.
+        let qualities = b"?+I55G#@";
+        let (compressed, merged) =
+            homopolymer_compress_fastq(sequence, qualities, QualityMerge::Mean, None).unwrap();
+        assert_eq!(compressed, b"ACGT");
+        assert_eq!(merged, b"<5>2");
+
+        let bins = QualityBins::illumina();
+        let (compressed, binned) =
+            homopolymer_compress_fastq(sequence, qualities, QualityMerge::Mean, Some(&bins))
+                .unwrap();
+        assert_eq!(binned.len(), compressed.len());
+        let representatives = [6, 15, 22, 27, 33, 37, 40].map(|score| score + PHRED_OFFSET);
+        assert!(binned
+            .iter()
+            .all(|quality| representatives.contains(quality)));
+        assert_eq!(binned, b"<7<0");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("median".parse(), Ok(QualityMerge::Median));
+        assert!("average".parse::<QualityMerge>().is_err());
+        assert_eq!(QualityMerge::Max.to_string(), "max");
+    }
+}
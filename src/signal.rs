@@ -0,0 +1,92 @@
+//! Homopolymer compression of noisy numeric signals, where runs consist of approximately equal values.
+
+/// The value that each item is compared against in [`homopolymer_compress_approx`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApproxRef {
+    /// Compare against the first value of the current run.
+    /// All values of a run are then within the tolerance of its first value.
+    Leader,
+    /// Compare against the immediately preceding value.
+    /// Runs can drift, since only consecutive values need to be within the tolerance,
+    /// e.g. a slow ramp forms a single run, even though its first and last value are far apart.
+    Previous,
+}
+
+/// Homopolymer compress the given signal, where a value continues the current run if it differs by at most `epsilon` from its reference value.
+///
+/// Each run is replaced by its first value.
+/// With an `epsilon` of zero, this is equal to homopolymer compression with exact comparison.
+/// `NaN` values never continue a run and always end the current run, since they are not within any tolerance of any value.
+///
+/// Panics if `epsilon` is negative or `NaN`.
+pub fn homopolymer_compress_approx(input: &[f64], epsilon: f64, reference: ApproxRef) -> Vec<f64> {
+    assert!(epsilon >= 0.0, "epsilon must be non-negative");
+    let mut compressed = Vec::new();
+    let mut reference_value: Option<f64> = None;
+    for &value in input {
+        let continues_run = reference_value
+            .map(|reference_value| (value - reference_value).abs() <= epsilon)
+            .unwrap_or(false);
+        if !continues_run {
+            compressed.push(value);
+            reference_value = Some(value);
+        } else if reference == ApproxRef::Previous {
+            reference_value = Some(value);
+        }
+    }
+    compressed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::signal::{homopolymer_compress_approx, ApproxRef};
+
+    #[test]
+    fn test_ramp() {
+        let ramp: Vec<_> = (0..=10).map(f64::from).collect();
+        assert_eq!(
+            homopolymer_compress_approx(&ramp, 1.5, ApproxRef::Leader),
+            [0.0, 2.0, 4.0, 6.0, 8.0, 10.0]
+        );
+        // Each step is within the tolerance, so the whole ramp drifts into a single run.
+        assert_eq!(
+            homopolymer_compress_approx(&ramp, 1.5, ApproxRef::Previous),
+            [0.0]
+        );
+        assert_eq!(
+            homopolymer_compress_approx(&ramp, 0.0, ApproxRef::Leader),
+            ramp
+        );
+    }
+
+    #[test]
+    fn test_noisy_plateau() {
+        // Quarter steps are exact in floating point, so the tolerances are not affected by rounding.
+        let signal = [5.0, 5.25, 4.75, 5.0, 5.5, 8.0, 8.25, 7.75];
+        assert_eq!(
+            homopolymer_compress_approx(&signal, 0.5, ApproxRef::Leader),
+            [5.0, 8.0]
+        );
+        assert_eq!(
+            homopolymer_compress_approx(&signal, 0.5, ApproxRef::Previous),
+            [5.0, 8.0]
+        );
+        assert_eq!(
+            homopolymer_compress_approx(&signal, 0.25, ApproxRef::Leader),
+            [5.0, 5.5, 8.0]
+        );
+        assert_eq!(
+            homopolymer_compress_approx(&signal, 0.25, ApproxRef::Previous),
+            [5.0, 4.75, 5.5, 8.0, 7.75]
+        );
+    }
+
+    #[test]
+    fn test_nan() {
+        let compressed =
+            homopolymer_compress_approx(&[1.0, f64::NAN, f64::NAN, 1.0], 0.5, ApproxRef::Leader);
+        assert_eq!(compressed.len(), 4);
+        assert!(compressed[1].is_nan() && compressed[2].is_nan());
+        assert!(homopolymer_compress_approx(&[], 0.5, ApproxRef::Previous).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+//! SIMD-accelerated homopolymer compression of byte sequences.
+//!
+//! On `x86_64`, sixteen bytes are compared to their predecessors at once using SSE2, which is always available on this architecture.
+//! On other architectures, the functions fall back to a scalar implementation.
+
+/// Homopolymer compress the given byte sequence and compute the length of each homopolymer run.
+///
+/// The output is the same as collecting [`homopolymer_compress_with_run_lengths`](crate::homopolymer_compress_with_run_lengths),
+/// but the run boundaries are detected sixteen bytes at a time, which is much faster for long runs.
+pub fn homopolymer_compress_with_run_lengths_simd(input: &[u8]) -> Vec<(u8, usize)> {
+    let mut runs = Vec::new();
+    if input.is_empty() {
+        return runs;
+    }
+
+    // A boundary is a position whose byte differs from its predecessor, i.e. the start of a run other than the first.
+    let mut run_start = 0;
+    let mut position = 1;
+    #[cfg(target_arch = "x86_64")]
+    while position + 16 <= input.len() {
+        let mut boundaries = boundary_mask(input, position);
+        runs.reserve(boundaries.count_ones() as usize);
+        while boundaries != 0 {
+            let boundary = position + boundaries.trailing_zeros() as usize;
+            runs.push((input[run_start], boundary - run_start));
+            run_start = boundary;
+            boundaries &= boundaries - 1;
+        }
+        position += 16;
+    }
+    for boundary in position..input.len() {
+        if input[boundary] != input[boundary - 1] {
+            runs.push((input[run_start], boundary - run_start));
+            run_start = boundary;
+        }
+    }
+    runs.push((input[run_start], input.len() - run_start));
+    runs
+}
+
+/// Compute a mask of the sixteen bytes starting at `position`, where bit `i` is set if the byte at `position + i` differs from its predecessor.
+///
+/// Panics if `position` is zero or there are less than sixteen bytes starting at `position`.
+#[cfg(target_arch = "x86_64")]
+fn boundary_mask(input: &[u8], position: usize) -> u32 {
+    use std::arch::x86_64::{__m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8};
+
+    assert!(position > 0 && position + 16 <= input.len());
+    // SAFETY: SSE2 is always available on x86_64, and both unaligned loads are in bounds by the assertion above.
+    let equal = unsafe {
+        let current = _mm_loadu_si128(input.as_ptr().add(position) as *const __m128i);
+        let previous = _mm_loadu_si128(input.as_ptr().add(position - 1) as *const __m128i);
+        _mm_movemask_epi8(_mm_cmpeq_epi8(current, previous)) as u32
+    };
+    !equal & 0xFFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::homopolymer_compress_with_run_lengths;
+    use crate::simd::homopolymer_compress_with_run_lengths_simd;
+    use crate::test_util::random_generator;
+
+    #[test]
+    fn test_run_lengths_simd() {
+        let mut random = random_generator(42);
+
+        for _ in 0..1000 {
+            let len = random() % 200;
+            let alphabet_size = 1 + random() % 4;
+            let max_run = 1 + random() % 40;
+            let mut input = Vec::new();
+            while input.len() < len {
+                let base = b"ACGT"[random() % alphabet_size];
+                input.extend(std::iter::repeat(base).take(1 + random() % max_run));
+            }
+            let expected: Vec<_> =
+                homopolymer_compress_with_run_lengths(input.iter().copied()).collect();
+            assert_eq!(homopolymer_compress_with_run_lengths_simd(&input), expected);
+        }
+    }
+
+    #[test]
+    fn test_run_lengths_simd_long_runs() {
+        let input = [vec![b'A'; 1000], vec![b'C'; 17], vec![b'A'; 16]].concat();
+        assert_eq!(
+            homopolymer_compress_with_run_lengths_simd(&input),
+            [(b'A', 1000), (b'C', 17), (b'A', 16)]
+        );
+        assert!(homopolymer_compress_with_run_lengths_simd(&[]).is_empty());
+        assert_eq!(
+            homopolymer_compress_with_run_lengths_simd(b"G"),
+            [(b'G', 1)]
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by the tests of multiple modules.
+
+/// A linear congruential generator, to get reproducible pseudo-random inputs without extra dependencies.
+pub fn random_generator(seed: u64) -> impl FnMut() -> usize {
+    let mut state = seed;
+    move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as usize
+    }
+}
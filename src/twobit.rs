@@ -0,0 +1,401 @@
+//! Reading and writing sequences in UCSC `.2bit` files.
+//!
+//! A `.2bit` file starts with a header containing a signature, which also determines the byte order of all integers in the file,
+//! and an index of the names and offsets of all sequences.
+//! Each sequence is stored with two bits per base, together with a list of blocks of `N`s and a list of soft-masked blocks.
+
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+/// The signature of a `.2bit` file, as read in the byte order of the file.
+const SIGNATURE: u32 = 0x1A41_2743;
+
+/// The bases encoded by the two-bit values 0 to 3.
+const BASES: [u8; 4] = *b"TCAG";
+
+/// A sequence read from a `.2bit` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TwoBitRecord {
+    /// The name of the sequence.
+    pub name: String,
+    /// The bases of the sequence, where `N` blocks are `N`s and soft-masked blocks are lower case.
+    pub sequence: Vec<u8>,
+}
+
+/// A reader for `.2bit` files, iterating over all sequences in the order of the index.
+pub struct TwoBitReader<R> {
+    reader: R,
+    big_endian: bool,
+    index: std::vec::IntoIter<(String, u64)>,
+}
+
+impl<R: Read + Seek> TwoBitReader<R> {
+    /// Create a new reader, reading the header and the sequence index immediately.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut signature = [0; 4];
+        reader.read_exact(&mut signature)?;
+        let big_endian = if u32::from_le_bytes(signature) == SIGNATURE {
+            false
+        } else if u32::from_be_bytes(signature) == SIGNATURE {
+            true
+        } else {
+            return Err(Error::new(ErrorKind::InvalidData, "not a 2bit file"));
+        };
+
+        let mut result = Self {
+            reader,
+            big_endian,
+            index: Vec::new().into_iter(),
+        };
+        let version = result.read_u32()?;
+        if version != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported 2bit version {version}"),
+            ));
+        }
+        let sequence_count = result.read_u32()?;
+        let _reserved = result.read_u32()?;
+
+        let mut index = Vec::new();
+        for _ in 0..sequence_count {
+            let mut name_len = [0];
+            result.reader.read_exact(&mut name_len)?;
+            let mut name = vec![0; usize::from(name_len[0])];
+            result.reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+            let offset = result.read_u32()?;
+            index.push((name, u64::from(offset)));
+        }
+        result.index = index.into_iter();
+        Ok(result)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn read_usize(&mut self) -> Result<usize> {
+        usize::try_from(self.read_u32()?)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "integer too large"))
+    }
+
+    /// Read a list of blocks, given as the number of blocks, followed by all starts and then all lengths.
+    fn read_blocks(&mut self) -> Result<Vec<(usize, usize)>> {
+        let count = self.read_usize()?;
+        let starts = (0..count)
+            .map(|_| self.read_usize())
+            .collect::<Result<Vec<_>>>()?;
+        starts
+            .into_iter()
+            .map(|start| Ok((start, self.read_usize()?)))
+            .collect()
+    }
+
+    fn read_record(&mut self, name: String, offset: u64) -> Result<TwoBitRecord> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let len = self.read_usize()?;
+        let n_blocks = self.read_blocks()?;
+        let mask_blocks = self.read_blocks()?;
+        let _reserved = self.read_u32()?;
+
+        let mut packed = vec![0; (len + 3) / 4];
+        self.reader.read_exact(&mut packed)?;
+        let mut sequence: Vec<_> = packed
+            .iter()
+            .flat_map(|byte| {
+                [6, 4, 2, 0]
+                    .into_iter()
+                    .map(move |shift| BASES[usize::from((byte >> shift) & 0b11)])
+            })
+            .take(len)
+            .collect();
+
+        for (start, block_len) in n_blocks {
+            block(&mut sequence, start, block_len)?.fill(b'N');
+        }
+        for (start, block_len) in mask_blocks {
+            block(&mut sequence, start, block_len)?.make_ascii_lowercase();
+        }
+
+        Ok(TwoBitRecord { name, sequence })
+    }
+}
+
+/// The block of the given sequence with the given start and length.
+fn block(sequence: &mut [u8], start: usize, len: usize) -> Result<&mut [u8]> {
+    start
+        .checked_add(len)
+        .and_then(|end| sequence.get_mut(start..end))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "block exceeds sequence"))
+}
+
+impl<R: Read + Seek> Iterator for TwoBitReader<R> {
+    type Item = Result<TwoBitRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, offset) = self.index.next()?;
+        Some(self.read_record(name, offset))
+    }
+}
+
+/// A writer for little-endian `.2bit` files.
+///
+/// Since the header of a `.2bit` file contains the offsets of all sequences, the records are held in memory in their encoded form
+/// until [`TwoBitWriter::finish`] writes the whole file.
+/// `N` and all other bases than `A`, `C`, `G` and `T` are stored as `N` blocks, and lower case bases are stored as soft-masked blocks.
+pub struct TwoBitWriter<W> {
+    writer: W,
+    records: Vec<(String, Vec<u8>)>,
+}
+
+impl<W: Write> TwoBitWriter<W> {
+    /// Create a new writer that writes to the given writer when finished.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            records: Vec::new(),
+        }
+    }
+
+    /// Encode the given record and keep it in memory until finishing.
+    pub fn write_record(&mut self, name: &str, sequence: &[u8]) -> Result<()> {
+        if name.len() > usize::from(u8::MAX) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("2bit sequence names must be at most 255 bytes long, but got {name}"),
+            ));
+        }
+
+        let mut record = Vec::new();
+        write_u32(&mut record, sequence.len())?;
+        let is_n = |base: &u8| !matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T');
+        for blocks in [
+            blocks(sequence, is_n),
+            blocks(sequence, u8::is_ascii_lowercase),
+        ] {
+            write_u32(&mut record, blocks.len())?;
+            for &(start, _) in &blocks {
+                write_u32(&mut record, start)?;
+            }
+            for &(_, len) in &blocks {
+                write_u32(&mut record, len)?;
+            }
+        }
+        write_u32(&mut record, 0)?;
+        record.extend(sequence.chunks(4).map(|chunk| {
+            chunk
+                .iter()
+                .zip([6, 4, 2, 0])
+                .fold(0, |byte, (base, shift)| {
+                    let code = match base.to_ascii_uppercase() {
+                        b'C' => 1,
+                        b'A' => 2,
+                        b'G' => 3,
+                        _ => 0,
+                    };
+                    byte | code << shift
+                })
+        }));
+
+        self.records.push((name.to_owned(), record));
+        Ok(())
+    }
+
+    /// Write the header, the index and all records, and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let index_len: usize = self
+            .records
+            .iter()
+            .map(|(name, _)| 1 + name.len() + 4)
+            .sum();
+        let mut header = Vec::new();
+        for value in [SIGNATURE as usize, 0, self.records.len(), 0] {
+            write_u32(&mut header, value)?;
+        }
+        let mut offset = header.len() + index_len;
+        for (name, record) in &self.records {
+            header.push(name.len() as u8);
+            header.extend_from_slice(name.as_bytes());
+            write_u32(&mut header, offset)?;
+            offset += record.len();
+        }
+
+        self.writer.write_all(&header)?;
+        for (_, record) in &self.records {
+            self.writer.write_all(record)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Append the given value as a little-endian `u32`, failing if it does not fit.
+fn write_u32(output: &mut Vec<u8>, value: usize) -> Result<()> {
+    let value = u32::try_from(value).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "2bit files must be smaller than 4 GiB",
+        )
+    })?;
+    output.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+/// The maximal blocks of bases of the given sequence that fulfil the given predicate, as starts and lengths.
+fn blocks(sequence: &[u8], predicate: impl Fn(&u8) -> bool) -> Vec<(usize, usize)> {
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for (position, base) in sequence.iter().enumerate() {
+        if predicate(base) {
+            match blocks.last_mut() {
+                Some((start, len)) if *start + *len == position => *len += 1,
+                _ => blocks.push((position, 1)),
+            }
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::twobit::{TwoBitReader, TwoBitRecord, TwoBitWriter};
+    use std::io::Cursor;
+
+    /// A record given as name, upper case bases, `N` blocks and soft-masked blocks.
+    type Record<'a> = (&'a str, &'a [u8], &'a [(u32, u32)], &'a [(u32, u32)]);
+
+    /// Encode the given records as a little-endian `.2bit` file.
+    fn encode(records: &[Record]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (_, sequence, n_blocks, mask_blocks) in records {
+            let mut record = Vec::new();
+            record.extend_from_slice(&(sequence.len() as u32).to_le_bytes());
+            for blocks in [n_blocks, mask_blocks] {
+                record.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+                for (start, _) in blocks.iter() {
+                    record.extend_from_slice(&start.to_le_bytes());
+                }
+                for (_, len) in blocks.iter() {
+                    record.extend_from_slice(&len.to_le_bytes());
+                }
+            }
+            record.extend_from_slice(&0u32.to_le_bytes());
+            for chunk in sequence.chunks(4) {
+                let mut byte = 0;
+                for (index, base) in chunk.iter().enumerate() {
+                    let code = match base {
+                        b'T' | b'N' => 0,
+                        b'C' => 1,
+                        b'A' => 2,
+                        b'G' => 3,
+                        _ => unreachable!(),
+                    };
+                    byte |= code << (6 - 2 * index);
+                }
+                record.push(byte);
+            }
+            data.push(record);
+        }
+
+        let mut file = Vec::new();
+        for value in [0x1A41_2743u32, 0, records.len() as u32, 0] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        let index_len: usize = records.iter().map(|(name, ..)| 1 + name.len() + 4).sum();
+        let mut offset = file.len() + index_len;
+        for ((name, ..), record) in records.iter().zip(&data) {
+            file.push(name.len() as u8);
+            file.extend_from_slice(name.as_bytes());
+            file.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += record.len();
+        }
+        for record in data {
+            file.extend_from_slice(&record);
+        }
+        file
+    }
+
+    #[test]
+    fn test_read() {
+        let file = encode(&[
+            ("chr1", b"ACGTTGCAA", &[(2, 3)], &[(0, 2), (7, 2)]),
+            ("chr2", b"", &[], &[]),
+            ("chrM", b"GGGG", &[], &[]),
+        ]);
+        let records: Vec<_> = TwoBitReader::new(Cursor::new(file))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            records,
+            [
+                TwoBitRecord {
+                    name: "chr1".to_string(),
+                    sequence: b"acNNNGCaa".to_vec(),
+                },
+                TwoBitRecord {
+                    name: "chr2".to_string(),
+                    sequence: Vec::new(),
+                },
+                TwoBitRecord {
+                    name: "chrM".to_string(),
+                    sequence: b"GGGG".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let mut writer = TwoBitWriter::new(Vec::new());
+        for (name, sequence) in [
+            ("chr1", &b"acNNNGCaa"[..]),
+            ("chr2", b""),
+            ("chrM", b"GGGGT"),
+            ("chrUn", b"ACRYnnt"),
+        ] {
+            writer.write_record(name, sequence).unwrap();
+        }
+        let file = writer.finish().unwrap();
+
+        let records: Vec<_> = TwoBitReader::new(Cursor::new(file))
+            .unwrap()
+            .map(|record| {
+                let record = record.unwrap();
+                (record.name, record.sequence)
+            })
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("chr1".to_string(), b"acNNNGCaa".to_vec()),
+                ("chr2".to_string(), Vec::new()),
+                ("chrM".to_string(), b"GGGGT".to_vec()),
+                ("chrUn".to_string(), b"ACNNnnt".to_vec()),
+            ]
+        );
+
+        let mut writer = TwoBitWriter::new(Vec::new());
+        assert!(writer.write_record(&"x".repeat(256), b"ACGT").is_err());
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(TwoBitReader::new(Cursor::new(b">chr1\nACGT\n".to_vec())).is_err());
+
+        let file = encode(&[("chr1", b"ACGT", &[(2, 3)], &[])]);
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+        assert!(reader.next().unwrap().is_err());
+
+        let mut file = encode(&[("chr1", b"ACGTA", &[], &[])]);
+        file.truncate(file.len() - 1);
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Tests of the command line interface, running the compiled binary on files in a scratch directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Create an empty scratch directory that is unique to the given test.
+fn scratch_directory(test: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "homopolymer-compress-{}-{test}",
+        std::process::id()
+    ));
+    if directory.exists() {
+        fs::remove_dir_all(&directory).unwrap();
+    }
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Run the binary with the given arguments in the given directory.
+fn run(directory: &Path, arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_homopolymer-compress"))
+        .current_dir(directory)
+        .args(arguments)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_inputs() {
+    let directory = scratch_directory("inputs");
+    fs::write(directory.join("a.fa"), ">a\nAACCGT\n").unwrap();
+    fs::write(directory.join("b.fa"), ">b\nTTTA\n").unwrap();
+    fs::create_dir(directory.join("out")).unwrap();
+
+    let output = run(
+        &directory,
+        &[
+            "--inputs",
+            "a.fa",
+            "b.fa",
+            "--output-directory",
+            "out",
+            "--file-parallel",
+            "2",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read_to_string(directory.join("out/a.fa")).unwrap(),
+        ">a\nACGT\n"
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join("out/b.fa")).unwrap(),
+        ">b\nTA\n"
+    );
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_inputs_overwriting_themselves() {
+    let directory = scratch_directory("inputs-overwriting-themselves");
+    fs::write(directory.join("a.fa"), ">a\nAACCGT\n").unwrap();
+    fs::write(directory.join("b.fa"), ">b\nTTTA\n").unwrap();
+
+    let output = run(
+        &directory,
+        &["--inputs", "a.fa", "b.fa", "--output-directory", "."],
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read_to_string(directory.join("a.fa")).unwrap(),
+        ">a\nAACCGT\n"
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join("b.fa")).unwrap(),
+        ">b\nTTTA\n"
+    );
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_inputs_with_same_name() {
+    let directory = scratch_directory("inputs-with-same-name");
+    for subdirectory in ["d1", "d2", "out"] {
+        fs::create_dir(directory.join(subdirectory)).unwrap();
+    }
+    fs::write(directory.join("d1/s.fa"), ">first\nAACCGT\n").unwrap();
+    fs::write(directory.join("d2/s.fa"), ">second\nTTTA\n").unwrap();
+
+    let output = run(
+        &directory,
+        &[
+            "--inputs",
+            "d1/s.fa",
+            "d2/s.fa",
+            "--output-directory",
+            "out",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(!directory.join("out/s.fa").exists());
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_zero_file_parallel() {
+    let directory = scratch_directory("zero-file-parallel");
+    fs::write(directory.join("a.fa"), ">a\nAACCGT\n").unwrap();
+    fs::create_dir(directory.join("out")).unwrap();
+
+    let output = run(
+        &directory,
+        &[
+            "--inputs",
+            "a.fa",
+            "--output-directory",
+            "out",
+            "--file-parallel",
+            "0",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(!directory.join("out/a.fa").exists());
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_run_outputs_with_only() {
+    let directory = scratch_directory("run-outputs-with-only");
+    fs::write(directory.join("in.fa"), ">r1\nCAAAAAAAAAAAAG\n").unwrap();
+
+    // The run of `A` is not compressed, but the run outputs still describe it as a single run.
+    let output = run(
+        &directory,
+        &[
+            "in.fa",
+            "out.fa",
+            "--only",
+            "C",
+            "--runs-tsv",
+            "runs.tsv",
+            "--long-runs-bed",
+            "long_runs.bed",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read_to_string(directory.join("out.fa")).unwrap(),
+        ">r1\nCAAAAAAAAAAAAG\n"
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join("runs.tsv")).unwrap(),
+        "record_id\trun_index\tbase\tstart\tlength\nr1\t0\tC\t0\t1\nr1\t1\tA\t1\t12\nr1\t2\tG\t13\t1\n"
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join("long_runs.bed")).unwrap(),
+        "r1\t1\t13\tA\t12\n"
+    );
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_run_outputs_with_max_run() {
+    let directory = scratch_directory("run-outputs-with-max-run");
+    fs::write(directory.join("in.fa"), ">r1\nCAAAAAAAAAAAAG\n").unwrap();
+
+    // The run of `A` is shortened to two items, but the run outputs still describe it as a single run.
+    let output = run(
+        &directory,
+        &[
+            "in.fa",
+            "out.fa",
+            "--max-run",
+            "2",
+            "--runs-tsv",
+            "runs.tsv",
+            "--long-runs-bed",
+            "long_runs.bed",
+            "--cigar",
+            "cigar.tsv",
+            "--bedgraph",
+            "runs.bedgraph",
+            "--dump-map",
+            "dump_map.txt",
+            "--run-length-histogram",
+            "histogram.tsv",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let read = |file: &str| fs::read_to_string(directory.join(file)).unwrap();
+    assert_eq!(read("out.fa"), ">r1\nCAAG\n");
+    assert_eq!(
+        read("runs.tsv"),
+        "record_id\trun_index\tbase\tstart\tlength\nr1\t0\tC\t0\t1\nr1\t1\tA\t1\t12\nr1\t2\tG\t13\t1\n"
+    );
+    assert_eq!(read("long_runs.bed"), "r1\t1\t13\tA\t12\n");
+    assert_eq!(read("cigar.tsv"), "r1\t1C12A1G\n");
+    assert!(read("runs.bedgraph").contains("r1\t1\t13\t12\n"));
+    assert_eq!(read("dump_map.txt"), "r1: 1 12 1\n");
+    assert!(read("histogram.tsv").contains("A\t12\t1\n"));
+    fs::remove_dir_all(directory).unwrap();
+}